@@ -0,0 +1,453 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use crate::networking::data::{ReadStateEntry, Snowflake, channel::{ChannelInfo, ChannelPermissionFlags}, event::{DispatchEvent, ReadyData, ReadyGuild}, guild::{GuildInfo, GuildMemberInfo, GuildRoleInfo, PresenceUpdateEventInfo, VoiceState, activity::ActivityInfo}, user::{UserInfo, UserRelationship, UserSettings}};
+
+/// Everything received from the gateway that is still known to be true, shared by the message handler and the UI.
+/// It's filled from READY and kept up to date by calling `update` with every dispatch event
+#[derive(Default)]
+pub struct Cache {
+    state: RwLock<CacheState>
+}
+
+/// The cached guilds don't contain their roles, members, channels, presences or voice states, those are kept in their own maps
+#[derive(Default)]
+struct CacheState {
+    current_user: Option<UserInfo>,
+    /// the settings of the current user, bots don't have any
+    user_settings: Option<UserSettings>,
+    guilds: HashMap<Snowflake, GuildInfo>,
+    /// guild channels and DMs
+    channels: HashMap<Snowflake, ChannelInfo>,
+    /// the roles of every guild by their id
+    roles: HashMap<Snowflake, HashMap<Snowflake, GuildRoleInfo>>,
+    /// the members of every guild by their user id
+    members: HashMap<Snowflake, HashMap<Snowflake, GuildMemberInfo>>,
+    users: HashMap<Snowflake, UserInfo>,
+    /// the last presence received for every user
+    presences: HashMap<Snowflake, Presence>,
+    /// the users connected to a voice channel in every guild by their user id
+    voice_states: HashMap<Snowflake, HashMap<Snowflake, VoiceState>>,
+    /// friends, blocked users and friend requests by the id of the other user
+    relationships: HashMap<Snowflake, UserRelationship>,
+    /// the last read message in every channel by the channel id
+    read_states: HashMap<Snowflake, ReadStateEntry>
+}
+
+/// The status of a user, presences are sent per guild but the last one is kept for the user
+#[derive(Debug, Clone)]
+pub struct Presence {
+    /// either "idle", "dnd", "online", or "offline"
+    pub status: String,
+    pub activities: Vec<ActivityInfo>
+}
+
+impl Presence {
+    fn new(presence: &PresenceUpdateEventInfo) -> Self {
+        Presence {
+            status: presence.status.clone().unwrap_or_else(|| "offline".to_string()),
+            activities: presence.activities.clone().unwrap_or_default()
+        }
+    }
+}
+
+impl CacheState {
+    /// Caches a user, users that only contain an id don't replace a complete one
+    fn add_user(&mut self, user: &UserInfo) {
+        if user.username.is_some() || !self.users.contains_key(&user.id) {
+            self.users.insert(user.id, user.clone());
+        }
+    }
+
+    fn add_channel(&mut self, channel: &ChannelInfo) {
+        for user in &channel.recipients {
+            self.add_user(user);
+        }
+        self.channels.insert(channel.id, channel.clone());
+    }
+
+    /// Caches a member of a guild and its user, members without a user id can't be looked up so they're ignored
+    fn add_member(&mut self, guild_id: &Snowflake, member: &GuildMemberInfo) {
+        if let Some(user) = &member.user {
+            self.add_user(user);
+        }
+        if let Some(user_id) = member.user_id() {
+            self.members.entry(*guild_id).or_default().insert(user_id, member.clone());
+        }
+    }
+
+    fn add_presence(&mut self, presence: &PresenceUpdateEventInfo) {
+        if let Some(user) = &presence.user {
+            self.add_user(user);
+            self.presences.insert(user.id, Presence::new(presence));
+        }
+    }
+
+    /// Caches a voice state, users that left the voice channel are removed
+    fn set_voice_state(&mut self, guild_id: &Snowflake, voice_state: &VoiceState) {
+        if let Some(user) = voice_state.member.as_ref().and_then(|member| member.user.as_ref()) {
+            self.add_user(user);
+        }
+
+        let guild_voice_states = self.voice_states.entry(*guild_id).or_default();
+        if voice_state.channel_id.is_some() {
+            let mut voice_state = voice_state.clone();
+            voice_state.guild_id = Some(*guild_id);
+            guild_voice_states.insert(voice_state.user_id, voice_state);
+        } else {
+            guild_voice_states.remove(&voice_state.user_id);
+        }
+    }
+
+    fn set_roles(&mut self, guild_id: &Snowflake, roles: &[GuildRoleInfo]) {
+        let roles = roles.iter()
+            .filter_map(|role| Some((role.id?, role.clone())))
+            .collect();
+        self.roles.insert(*guild_id, roles);
+    }
+
+    /// Caches a guild from READY or GUILD_CREATE, replacing everything that was known about it
+    fn add_guild(&mut self, guild: &GuildInfo) {
+        let mut guild = guild.clone();
+        self.remove_guild(&guild.id);
+
+        // Channels in GUILD_CREATE don't have a guild id
+        for mut channel in guild.channels.take().unwrap_or_default() {
+            channel.guild_id = Some(guild.id);
+            self.add_channel(&channel);
+        }
+        for member in guild.members.take().unwrap_or_default() {
+            self.add_member(&guild.id, &member);
+        }
+        for presence in guild.presences.take().unwrap_or_default() {
+            self.add_presence(&presence);
+        }
+        for voice_state in guild.voice_states.take().unwrap_or_default() {
+            self.set_voice_state(&guild.id, &voice_state);
+        }
+        self.set_roles(&guild.id, &guild.roles);
+        guild.roles.clear();
+
+        self.guilds.insert(guild.id, guild);
+    }
+
+    /// Forgets a guild and everything in it
+    fn remove_guild(&mut self, id: &Snowflake) {
+        self.guilds.remove(id);
+        self.roles.remove(id);
+        self.members.remove(id);
+        self.voice_states.remove(id);
+        self.channels.retain(|_, channel| channel.guild_id.as_ref() != Some(id));
+    }
+
+    /// Every shard gets its own READY, so only the guilds in it are replaced and the ones of the other shards are kept
+    fn ready(&mut self, ready: &ReadyData) {
+        self.add_user(&ready.user);
+        self.current_user = Some(ready.user.clone());
+        self.user_settings = ready.user_settings.clone();
+        for user in &ready.users {
+            self.add_user(user);
+        }
+        for guild in &ready.guilds {
+            match guild {
+                ReadyGuild::Available(guild) => self.add_guild(guild),
+                // Bots get their guilds in GUILD_CREATE events after READY, which replace them
+                ReadyGuild::Unavailable(guild) => if let Some(cached) = self.guilds.get_mut(&guild.id) {
+                    cached.unavailable = Some(true);
+                }
+            }
+        }
+        for channel in &ready.private_channels {
+            self.add_channel(channel);
+        }
+
+        // Only user accounts have relationships and read states, and they only have one shard
+        for relationship in &ready.relationships {
+            self.relationships.insert(relationship.id, relationship.clone());
+        }
+        if let Some(read_state) = &ready.read_state {
+            self.read_states = read_state.entries.iter()
+                .map(|read_state| (read_state.id, read_state.clone()))
+                .collect();
+        }
+    }
+
+    fn update(&mut self, event: &DispatchEvent) {
+        match event {
+            DispatchEvent::Ready(ready) => self.ready(ready),
+            DispatchEvent::MessageCreate(message) => {
+                self.add_user(&message.author);
+                // The member of the author doesn't contain the user
+                if let (Some(guild_id), Some(member)) = (&message.guild_id, &message.member) {
+                    let mut member = member.clone();
+                    member.user = Some(message.author.clone());
+                    self.add_member(guild_id, &member);
+                }
+                if let Some(channel) = self.channels.get_mut(&message.channel_id) {
+                    channel.last_message_id = Some(message.id);
+                }
+            },
+            DispatchEvent::MessageReactionAdd(reaction) => {
+                if let (Some(guild_id), Some(member)) = (&reaction.guild_id, &reaction.member) {
+                    self.add_member(guild_id, member);
+                }
+            },
+            DispatchEvent::ChannelPinsUpdate(data) => {
+                if let Some(channel) = self.channels.get_mut(&data.channel_id) {
+                    channel.last_pin_timestamp = data.last_pin_timestamp;
+                }
+            },
+            DispatchEvent::MessageAck(ack) => {
+                let read_state = self.read_states.entry(ack.channel_id).or_insert_with(|| ReadStateEntry {
+                    mention_count: 0,
+                    last_pin_timestamp: None,
+                    last_message_id: None,
+                    id: ack.channel_id
+                });
+                read_state.last_message_id = Some(ack.message_id);
+                read_state.mention_count = 0;
+            },
+            DispatchEvent::RelationshipAdd(data) => {
+                self.add_user(&data.user);
+                self.relationships.insert(data.id, UserRelationship {
+                    user_id: data.user.id,
+                    relationship_type: data.relationship_type,
+                    nickname: data.nickname.clone(),
+                    id: data.id
+                });
+            },
+            DispatchEvent::RelationshipRemove(data) => {
+                self.relationships.remove(&data.id);
+            },
+            DispatchEvent::PresenceUpdate(presence) => {
+                self.add_user(&presence.user);
+                self.presences.insert(presence.user.id, Presence {
+                    status: presence.status.clone(),
+                    activities: presence.activities.clone()
+                });
+            },
+            DispatchEvent::ChannelCreate(channel) | DispatchEvent::ChannelUpdate(channel) => self.add_channel(channel),
+            DispatchEvent::ChannelDelete(channel) => {
+                self.channels.remove(&channel.id);
+                self.read_states.remove(&channel.id);
+            },
+            DispatchEvent::ChannelRecipientAdd(data) => {
+                self.add_user(&data.user);
+                if let Some(channel) = self.channels.get_mut(&data.channel_id) {
+                    // Channels from READY only have the ids of their recipients
+                    match &mut channel.recipient_ids {
+                        Some(ids) => ids.push(data.user.id),
+                        None => channel.recipients.push(data.user.clone())
+                    }
+                }
+            },
+            DispatchEvent::ChannelRecipientRemove(data) => {
+                if let Some(channel) = self.channels.get_mut(&data.channel_id) {
+                    channel.recipients.retain(|user| user.id != data.user.id);
+                    if let Some(ids) = &mut channel.recipient_ids {
+                        ids.retain(|id| id != &data.user.id);
+                    }
+                }
+            },
+            DispatchEvent::GuildCreate(guild) => self.add_guild(guild),
+            DispatchEvent::GuildUpdate(guild) => {
+                // GUILD_UPDATE doesn't contain the fields that are only sent in GUILD_CREATE
+                let mut guild = (**guild).clone();
+                if let Some(existing) = self.guilds.get(&guild.id) {
+                    guild.joined_at = existing.joined_at;
+                    guild.large = existing.large;
+                    guild.member_count = existing.member_count;
+                }
+                self.set_roles(&guild.id, &guild.roles);
+                guild.roles.clear();
+                self.guilds.insert(guild.id, guild);
+            },
+            DispatchEvent::GuildDelete(data) => {
+                // Unavailable guilds are kept until they're sent again in a GUILD_CREATE
+                if data.unavailable.unwrap_or(false) {
+                    if let Some(guild) = self.guilds.get_mut(&data.id) {
+                        guild.unavailable = Some(true);
+                    }
+                } else {
+                    self.remove_guild(&data.id);
+                }
+            },
+            DispatchEvent::GuildMemberAdd(data) => {
+                self.add_member(&data.guild_id, &data.member);
+                if let Some(count) = self.guilds.get_mut(&data.guild_id).and_then(|guild| guild.member_count.as_mut()) {
+                    *count += 1;
+                }
+            },
+            DispatchEvent::GuildMemberUpdate(data) => {
+                self.add_user(&data.user);
+                let members = self.members.entry(data.guild_id).or_default();
+                match members.get_mut(&data.user.id) {
+                    Some(member) => {
+                        member.user = Some(data.user.clone());
+                        member.nick = data.nick.clone();
+                        member.roles = data.roles.clone();
+                        member.premium_since = data.premium_since;
+                        member.pending = data.pending;
+                    },
+                    // Members that aren't cached yet can only be added if it's known when they joined
+                    None => if let Some(joined_at) = data.joined_at {
+                        members.insert(data.user.id, GuildMemberInfo {
+                            user: Some(data.user.clone()),
+                            user_id: None,
+                            nick: data.nick.clone(),
+                            roles: data.roles.clone(),
+                            joined_at,
+                            premium_since: data.premium_since,
+                            deaf: false,
+                            mute: false,
+                            pending: data.pending,
+                            permission: None
+                        });
+                    }
+                }
+            },
+            DispatchEvent::GuildMemberRemove(data) => {
+                if let Some(members) = self.members.get_mut(&data.guild_id) {
+                    members.remove(&data.user.id);
+                }
+                if let Some(count) = self.guilds.get_mut(&data.guild_id).and_then(|guild| guild.member_count.as_mut()) {
+                    *count = (*count - 1).max(0);
+                }
+            },
+            DispatchEvent::GuildMembersChunk(data) => {
+                for member in &data.members {
+                    self.add_member(&data.guild_id, member);
+                }
+                for presence in data.presences.iter().flatten() {
+                    self.add_presence(presence);
+                }
+            },
+            DispatchEvent::GuildRoleCreate(data) | DispatchEvent::GuildRoleUpdate(data) => {
+                if let Some(id) = &data.role.id {
+                    self.roles.entry(data.guild_id).or_default().insert(*id, data.role.clone());
+                }
+            },
+            DispatchEvent::GuildRoleDelete(data) => {
+                if let Some(roles) = self.roles.get_mut(&data.guild_id) {
+                    roles.remove(&data.role_id);
+                }
+                for member in self.members.get_mut(&data.guild_id).into_iter().flat_map(|members| members.values_mut()) {
+                    member.roles.retain(|role| role != &data.role_id);
+                }
+            },
+            DispatchEvent::GuildEmojisUpdate(data) => {
+                if let Some(guild) = self.guilds.get_mut(&data.guild_id) {
+                    guild.emojis = data.emojis.clone();
+                }
+            },
+            DispatchEvent::VoiceStateUpdate(voice_state) => {
+                // Voice states outside of guilds are for calls in DMs, which aren't cached
+                if let Some(guild_id) = &voice_state.guild_id {
+                    self.set_voice_state(guild_id, voice_state);
+                }
+            },
+            DispatchEvent::UserSettingsUpdate(update) => {
+                if let Some(settings) = &mut self.user_settings {
+                    if let Some(timezone_offset) = update.timezone_offset {
+                        settings.timezone_offset = timezone_offset;
+                    }
+                    if let Some(status) = &update.status {
+                        settings.status = status.clone();
+                    }
+                }
+            },
+            DispatchEvent::UserUpdate(user) => {
+                self.add_user(user);
+                self.current_user = Some((**user).clone());
+            },
+            // Messages aren't cached
+            DispatchEvent::Resumed
+            | DispatchEvent::MessageUpdate(_)
+            | DispatchEvent::MessageDelete(_)
+            | DispatchEvent::MessageDeleteBulk(_)
+            | DispatchEvent::MessageReactionRemove(_)
+            | DispatchEvent::MessageReactionRemoveAll(_)
+            | DispatchEvent::MessageReactionRemoveEmoji(_)
+            | DispatchEvent::Unknown { .. } => {}
+        }
+    }
+}
+
+impl Cache {
+    /// Applies a dispatch event to the cache, READY replaces everything that was cached about its guilds
+    pub fn update(&self, event: &DispatchEvent) {
+        self.state.write().unwrap().update(event);
+    }
+
+    /// The user that is logged in
+    pub fn current_user(&self) -> Option<UserInfo> {
+        self.state.read().unwrap().current_user.clone()
+    }
+
+    /// The minutes the current user is behind UTC, used to show times in their timezone
+    pub fn timezone_offset(&self) -> i32 {
+        self.state.read().unwrap().user_settings.as_ref().map_or(0, |settings| settings.timezone_offset)
+    }
+
+    /// The permissions of the current user in a guild channel, computed from the cached roles and overwrites.
+    /// DMs and channels of guilds whose member isn't cached have none.
+    /// https://discord.com/developers/docs/topics/permissions#permission-overwrites
+    pub fn permissions(&self, channel_id: &Snowflake) -> ChannelPermissionFlags {
+        let state = self.state.read().unwrap();
+        let (channel, user) = match (state.channels.get(channel_id), &state.current_user) {
+            (Some(channel), Some(user)) => (channel, user),
+            _ => return ChannelPermissionFlags::empty()
+        };
+        let guild_id = match channel.guild_id {
+            Some(guild_id) => guild_id,
+            None => return ChannelPermissionFlags::empty()
+        };
+        if state.guilds.get(&guild_id).map(|guild| guild.owner_id) == Some(user.id) {
+            return ChannelPermissionFlags::all();
+        }
+        let (roles, member) = match (state.roles.get(&guild_id), state.members.get(&guild_id).and_then(|members| members.get(&user.id))) {
+            (Some(roles), Some(member)) => (roles, member),
+            _ => return ChannelPermissionFlags::empty()
+        };
+
+        // The @everyone role has the id of the guild
+        let mut permissions = std::iter::once(&guild_id).chain(&member.roles)
+            .filter_map(|role_id| roles.get(role_id))
+            .fold(ChannelPermissionFlags::empty(), |permissions, role| permissions | ChannelPermissionFlags::parse(&role.permissions));
+        if permissions.contains(ChannelPermissionFlags::ADMINISTRATOR) {
+            return ChannelPermissionFlags::all();
+        }
+
+        // The overwrite of @everyone applies first, then the ones of the member's roles together, then the one of the member
+        let overwrites = &channel.permission_overwrites;
+        let apply = |permissions: ChannelPermissionFlags, allow: ChannelPermissionFlags, deny: ChannelPermissionFlags| (permissions - deny) | allow;
+        if let Some(everyone) = overwrites.iter().find(|overwrite| overwrite.id == guild_id) {
+            permissions = apply(permissions, ChannelPermissionFlags::parse(&everyone.allow), ChannelPermissionFlags::parse(&everyone.deny));
+        }
+        let (allow, deny) = overwrites.iter()
+            .filter(|overwrite| overwrite.target_type == 0 && member.roles.contains(&overwrite.id))
+            .fold((ChannelPermissionFlags::empty(), ChannelPermissionFlags::empty()), |(allow, deny), overwrite| {
+                (allow | ChannelPermissionFlags::parse(&overwrite.allow), deny | ChannelPermissionFlags::parse(&overwrite.deny))
+            });
+        permissions = apply(permissions, allow, deny);
+        if let Some(own) = overwrites.iter().find(|overwrite| overwrite.target_type == 1 && overwrite.id == user.id) {
+            permissions = apply(permissions, ChannelPermissionFlags::parse(&own.allow), ChannelPermissionFlags::parse(&own.deny));
+        }
+        permissions
+    }
+
+    pub fn guild(&self, id: &Snowflake) -> Option<GuildInfo> {
+        self.state.read().unwrap().guilds.get(id).cloned()
+    }
+
+    pub fn channel(&self, id: &Snowflake) -> Option<ChannelInfo> {
+        self.state.read().unwrap().channels.get(id).cloned()
+    }
+
+    pub fn user(&self, id: &Snowflake) -> Option<UserInfo> {
+        self.state.read().unwrap().users.get(id).cloned()
+    }
+
+    pub fn presence(&self, user_id: &Snowflake) -> Option<Presence> {
+        self.state.read().unwrap().presences.get(user_id).cloned()
+    }
+}
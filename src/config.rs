@@ -0,0 +1,186 @@
+use std::{collections::HashMap, env, fmt, fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::networking::data::gateway::{GatewayEncoding, GatewayIntents};
+
+/// The config file used when no other one is given
+const DEFAULT_CONFIG_FILE: &str = "rustcord.json";
+
+/// The settings that can be changed with environment variables and command line arguments, by their argument name
+const SETTINGS: [&str; 7] = ["token", "intents", "shards", "gateway-url", "api-url", "api-version", "gateway-encoding"];
+
+/// Settings of the client.
+/// They're read from a json config file, then from `RUSTCORD_*` environment variables and then from `--*` command line arguments,
+/// each one overriding the previous one. The config file is `rustcord.json` unless `RUSTCORD_CONFIG` or `--config` say otherwise
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// the token of the account, bot tokens have to start with `Bot `
+    pub token: String,
+    /// the intents to identify with when using a bot token
+    pub intents: GatewayIntents,
+    /// the number of shards to connect with when using a bot token, the one recommended by the gateway is used if it isn't set
+    pub shards: Option<u32>,
+    /// the url of the gateway without the query, it's asked for from the REST api if it isn't set
+    pub gateway_url: Option<String>,
+    /// the url of the REST api, without the version
+    pub api_url: String,
+    /// the version of the gateway and the REST api
+    pub api_version: u8,
+    /// the format gateway payloads are sent in
+    pub gateway_encoding: GatewayEncoding
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            token: String::new(),
+            intents: GatewayIntents::all() - GatewayIntents::GUILD_MEMBERS - GatewayIntents::GUILD_PRESENCES,
+            shards: None,
+            gateway_url: None,
+            api_url: "https://discord.com/api".to_string(),
+            api_version: 8,
+            gateway_encoding: GatewayEncoding::Json
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config from the config file, the environment and the command line arguments
+    pub fn load() -> Result<Config, String> {
+        let args: Vec<(String, String)> = parse_args(env::args().skip(1))?;
+        let vars: HashMap<String, String> = env::vars().filter(|(var, _)| var.starts_with("RUSTCORD_")).collect();
+
+        // Find out which config file to use, only complain about it not existing if it was set explicitly
+        let config_file = args.iter()
+            .find(|(key, _)| key == "config")
+            .map(|(_, value)| value)
+            .or_else(|| vars.get("RUSTCORD_CONFIG"));
+        let config = match config_file {
+            Some(path) => Config::from_file(Path::new(path))?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Config::default()
+        };
+        let config = config.merge(&vars, &args)?;
+
+        if config.token.is_empty() {
+            return Err("No token was given".to_string());
+        }
+
+        Ok(config)
+    }
+
+    /// Overrides the settings of the config file with the `RUSTCORD_*` environment variables and then with the command line arguments
+    fn merge(mut self, vars: &HashMap<String, String>, args: &[(String, String)]) -> Result<Config, String> {
+        for key in &SETTINGS {
+            let var = format!("RUSTCORD_{}", key.to_uppercase().replace('-', "_"));
+            if let Some(value) = vars.get(&var) {
+                self.set(key, value).map_err(|e| format!("{}: {}", var, e))?;
+            }
+        }
+
+        for (key, value) in args.iter().filter(|(key, _)| key != "config") {
+            self.set(key, value).map_err(|e| format!("--{}: {}", key, e))?;
+        }
+
+        Ok(self)
+    }
+
+    fn from_file(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    /// Changes a setting from its name in the command line arguments
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "token" => self.token = value.to_string(),
+            "intents" => self.intents = value.parse().ok()
+                .and_then(GatewayIntents::from_bits)
+                .ok_or_else(|| format!("Invalid intents: {}", value))?,
+            "shards" => self.shards = Some(value.parse().map_err(|_| format!("Invalid number of shards: {}", value))?),
+            "gateway-url" => self.gateway_url = Some(value.to_string()),
+            "api-url" => self.api_url = value.to_string(),
+            "api-version" => self.api_version = value.parse().map_err(|_| format!("Invalid api version: {}", value))?,
+            "gateway-encoding" => self.gateway_encoding = value.parse()?,
+            _ => return Err(format!("Unknown setting: {}", key))
+        }
+
+        Ok(())
+    }
+
+    /// The url of the REST api including the version, like `https://discord.com/api/v8`
+    pub fn api_base(&self) -> String {
+        format!("{}/v{}", self.api_url.trim_end_matches('/'), self.api_version)
+    }
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The token is left out so it doesn't end up in logs
+        f.debug_struct("Config")
+            .field("intents", &self.intents)
+            .field("shards", &self.shards)
+            .field("gateway_url", &self.gateway_url)
+            .field("api_url", &self.api_url)
+            .field("api_version", &self.api_version)
+            .field("gateway_encoding", &self.gateway_encoding)
+            .finish()
+    }
+}
+
+/// Splits `--key value` and `--key=value` arguments into key and value pairs
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Vec<(String, String)>, String> {
+    let mut parsed = Vec::new();
+    while let Some(arg) = args.next() {
+        let arg = match arg.strip_prefix("--") {
+            Some(arg) => arg.to_string(),
+            None => return Err(format!("Unexpected argument: {}", arg))
+        };
+
+        match arg.split_once('=') {
+            Some((key, value)) => parsed.push((key.to_string(), value.to_string())),
+            None => {
+                let value = args.next().ok_or_else(|| format!("Missing value for --{}", arg))?;
+                parsed.push((arg, value));
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<(String, String)> {
+        parse_args(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn arguments_override_environment_and_file() {
+        let file: Config = serde_json::from_str(r#"{"token": "file", "gateway_url": "wss://file", "api_url": "https://file", "api_version": 9}"#).unwrap();
+        let vars: HashMap<String, String> = vec![
+            ("RUSTCORD_TOKEN".to_string(), "env".to_string()),
+            ("RUSTCORD_API_URL".to_string(), "https://env".to_string())
+        ].into_iter().collect();
+
+        let config = file.merge(&vars, &args(&["--config", "other.json", "--token=args", "--shards", "2"])).unwrap();
+        assert_eq!(config.token, "args");
+        assert_eq!(config.shards, Some(2));
+        assert_eq!(config.api_url, "https://env");
+        assert_eq!(config.gateway_url.as_deref(), Some("wss://file"));
+        assert_eq!(config.api_version, 9);
+        assert_eq!(config.gateway_encoding, GatewayEncoding::Json);
+    }
+
+    #[test]
+    fn invalid_settings_name_their_source() {
+        let vars: HashMap<String, String> = vec![("RUSTCORD_SHARDS".to_string(), "many".to_string())].into_iter().collect();
+        assert_eq!(Config::default().merge(&vars, &[]).err().unwrap(), "RUSTCORD_SHARDS: Invalid number of shards: many");
+        assert_eq!(Config::default().merge(&HashMap::new(), &args(&["--colour", "red"])).err().unwrap(), "--colour: Unknown setting: colour");
+        assert!(parse_args(vec!["token".to_string()].into_iter()).is_err());
+    }
+}
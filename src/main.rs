@@ -1,10 +1,12 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use fltk::prelude::BrowserExt;
-use futures_channel;
-use tokio::{self, sync::mpsc};
-use networking::{connect_to_discord, data::{gateway::GatewayPayload, receive::handle_messages, sendable}};
-use tokio_tungstenite::tungstenite::Message;
+use std::sync::Arc;
+
+use futures_util::{stream::FuturesUnordered, StreamExt};
+use tokio::{self, sync::mpsc, task::JoinHandle};
+use cache::Cache;
+use config::Config;
+use networking::{data::{gateway::GatewayCloseCode, receive::handle_messages}, http::HttpClient, shard::{ShardManager, ShardPayload}};
 
 #[macro_use]
 extern crate bitflags;
@@ -12,41 +14,66 @@ extern crate pretty_env_logger;
 #[macro_use]
 extern crate log;
 
+mod cache;
+mod config;
 mod networking;
 mod ui;
 
+/// How many received payloads can wait for the message handler before the shards stop reading
+const RECEIVE_BUFFER: usize = 256;
+
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
 
-    let token = String::from("Hidden from git");
-    
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid config: {}", e);
+            std::process::exit(1);
+        }
+    };
+    debug!("Using config: {:?}", config);
+
     // Build the client with default headers containing the auth token
     let mut headers = reqwest::header::HeaderMap::new();
-    headers.insert(reqwest::header::AUTHORIZATION, reqwest::header::HeaderValue::from_str(token.as_str()).unwrap());
+    headers.insert(reqwest::header::AUTHORIZATION, reqwest::header::HeaderValue::from_str(config.token.as_str()).unwrap());
     let client = reqwest::Client::builder()
         .default_headers(headers)
         .build().unwrap();
+    let http = HttpClient::new(client, config.api_base());
 
-    let mut ui = ui::RustcordUI::new();
-    ui.set_send_callback_to_discord(client.clone()); // Makes the send button send a message to discord instead of displaying a message
+    // Everything received from the gateway is cached so the UI and the message handler can look it up
+    let cache = Arc::new(Cache::default());
 
-    // Add a test item to the ui
-    let mut b = ui.clone();
-    b.chat_messages.add("item");
-    
-    // Make a mpsc for sending messages to discord and another for receiving messages from discord
-    let (send_tx, send_rx) = futures_channel::mpsc::unbounded::<Message>();
-    let (receive_tx, receive_rx) = mpsc::channel::<GatewayPayload>(32);
-    
-    tokio::spawn(connect(send_rx, receive_tx.clone())); // Spawn a thread to connect to the websocket
-    tokio::spawn(handle_messages(client.clone(), receive_rx, send_tx.clone(), ui.clone())); // Spawn a thread to handle the messages received from the websocket
-    tokio::spawn(sendable::send_identify(token.clone(), send_tx.clone())); // Spawn a thread to send an identify message to the websocket
+    let mut ui = ui::RustcordUI::new(cache.clone(), http.clone());
+    ui.set_send_callback_to_discord(); // Makes the send button send a message to discord instead of displaying a message
+
+    // Make a mpsc for receiving messages from every shard, each shard has its own sender for messages to discord
+    // Shards wait when it's full, so it's big enough for the burst of events after READY
+    let (receive_tx, receive_rx) = mpsc::channel::<ShardPayload>(RECEIVE_BUFFER);
+
+    // Connect every shard to the websocket, their sessions are kept so they can be resumed after reconnecting
+    let (shards, shard_handles) = ShardManager::start(http, config.clone(), receive_tx).await;
+
+    tokio::spawn(wait_for_shards(shard_handles, ui.clone())); // Spawn a thread to report shards that stopped
+    tokio::spawn(handle_messages(receive_rx, Arc::new(shards), cache, ui.clone())); // Spawn a thread to handle the messages received from the websocket, it identifies once the websocket says hello
     
     // Start the ui app
     ui.app.run().unwrap();
 }
 
-async fn connect(write_rx: futures_channel::mpsc::UnboundedReceiver<Message>, read_tx: mpsc::Sender<GatewayPayload>) {
-    connect_to_discord(write_rx, read_tx).await;
+async fn wait_for_shards(shard_handles: Vec<JoinHandle<Result<(), GatewayCloseCode>>>, mut ui: ui::RustcordUI) {
+    // Show why a shard stopped if it can't reconnect
+    let mut shard_handles: FuturesUnordered<_> = shard_handles.into_iter().collect();
+    while let Some(result) = shard_handles.next().await {
+        match result {
+            Ok(Ok(())) => {},
+            Ok(Err(close_code)) => {
+                error!("Disconnected from the gateway: {}", close_code);
+                ui.show_error(&close_code.to_string());
+            },
+            Err(e) => error!("A shard stopped unexpectedly: {}", e)
+        }
+    }
 }
\ No newline at end of file
@@ -1,69 +1,251 @@
-use data::{gateway::GatewayPayload};
+use std::{cmp, error::Error, sync::Arc, time::Duration};
+
+use crate::config::Config;
+use command_queue::{CommandQueue, NextCommand};
+use data::{gateway::{GatewayCloseAction, GatewayCloseCode, GatewayEncoding, GatewayOpCodes, GatewayPayload, GatewayPayloadData}};
 use futures_channel;
-use futures_util::{future, pin_mut, StreamExt};
-use tokio::{net::TcpStream, sync::mpsc};
+use futures_util::{future, pin_mut, SinkExt, StreamExt};
+use session::GatewaySession;
+use shard::ShardPayload;
+use tokio::{net::TcpStream, sync::mpsc::{self, error::TrySendError}};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::{self, Message, http::StatusCode}};
+use zlib::ZlibStream;
 
 pub mod data;
+pub mod http;
+pub mod session;
+pub mod shard;
+mod command_queue;
+mod discovery;
+mod etf;
+mod zlib;
+
+/// How long to wait before the first reconnection attempt
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// The longest the reconnection delay can grow to
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
 
-async fn connect_to_websocket(wss_url: &str) -> (WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::http::Response<()>) {
+async fn connect_to_websocket(wss_url: &str) -> Result<(WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::http::Response<()>), tungstenite::Error> {
     // Convert the input url from a string to a Url
     let url = url::Url::parse(wss_url).unwrap();
 
     debug!("Connecting to: {}", url);
-    let (ws_stream, response) = connect_async(url).await.expect("Failed to connect");
+    connect_async(url).await
+}
 
-    (ws_stream, response)
+/// Adds the query parameters the gateway needs to its url
+fn gateway_url(base_url: &str, config: &Config) -> String {
+    format!("{}/?v={}&encoding={}&compress=zlib-stream", base_url.trim_end_matches('/'), config.api_version, config.gateway_encoding.as_str())
 }
 
-async fn receive_message(message: Result<Message, tungstenite::Error>, read_tx: mpsc::Sender<GatewayPayload>) {
-    // Convert the message into text
-    let message = message.unwrap();
-    let text = message.into_text().unwrap();
+fn encode_payload(payload: &GatewayPayload, encoding: GatewayEncoding) -> Result<Message, Box<dyn Error>> {
+    Ok(match encoding {
+        GatewayEncoding::Json => Message::text(serde_json::to_string(payload)?),
+        GatewayEncoding::Etf => Message::binary(etf::to_vec(payload)?)
+    })
+}
 
+async fn receive_message(message: &[u8], encoding: GatewayEncoding, read_tx: mpsc::Sender<ShardPayload>, session: Arc<GatewaySession>) {
     trace!("Received a message from the gateway");
 
     // Try to parse the message
-    let data: Result<GatewayPayload, serde_json::Error> = serde_json::from_str(text.as_str());
+    let data = GatewayPayload::decode(message, encoding);
     match data { // Do stuff with the data from the message
         Ok(payload) => {
             trace!("Successfully parsed the payload");
-            let r = read_tx.try_send(payload);
-            if let Err(e) = r {
-                error!("Failed to send the data to read_tx: {:?}", e);
+
+            // Remember what's needed to resume the session if the connection drops
+            if let Some(sequence) = payload.s {
+                session.update_sequence(sequence);
+            }
+            // Recorded before waiting for the message handler, which would delay it and make the connection look dead
+            if payload.op == GatewayOpCodes::HeartbeatAck {
+                session.heartbeat_acked();
+            }
+            // The session is read on its own, so it can be resumed even if the rest of READY couldn't be decoded
+            if let Some(GatewayPayloadData::Dispatch(event)) = &payload.d {
+                if let Some(ready) = event.ready_session() {
+                    session.set_session(ready.session_id, ready.resume_gateway_url);
+                }
+            }
+
+            // Wait for the message handler to catch up instead of losing the event,
+            // reading from the websocket stops in the meantime
+            let counters = session.event_counters();
+            let delivered = match read_tx.try_send(ShardPayload { shard_id: session.shard_id(), connection: session.connection(), payload }) {
+                Ok(()) => true,
+                Err(TrySendError::Full(payload)) => {
+                    debug!("The message handler is behind, delaying the event ({} delayed so far)", counters.add_delayed());
+                    read_tx.send(payload).await.is_ok()
+                },
+                Err(TrySendError::Closed(_)) => false
+            };
+            if !delivered {
+                error!("Failed to send the data to read_tx, the message handler has stopped ({} dropped so far)", counters.add_dropped());
             }
-            // TODO: Do stuff with the payload
         },
         Err(error) => {
             warn!("Failed to parse the payload: {}", error);
-            warn!("Input is: {}", text);
+            warn!("Input is: {}", String::from_utf8_lossy(message));
         }
     }
 }
 
-/// `write_rx` receives messages to be sent to the websocket.
-/// Messages received from the websocket are sent to `read_tx` 
-pub async fn connect_to_discord(write_rx: futures_channel::mpsc::UnboundedReceiver<Message>, read_tx: mpsc::Sender<GatewayPayload>) {
-    let (ws_stream, response) = connect_to_websocket("wss://gateway.discord.gg/?encoding=json&v=8").await;
-    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
-        error!("Failed to connect to the websocket! {:?}", response);
-        return;
-    }
+/// Why a websocket connection stopped running
+enum ConnectionEnd {
+    /// the websocket was closed or failed, with the close code sent by the gateway if there was one
+    Closed(Option<GatewayCloseCode>),
+    /// a reconnect was requested through the session
+    Reconnect,
+    /// `write_rx` has no senders left, so the client is shutting down
+    Shutdown
+}
 
+/// Runs a single websocket connection until it's closed
+async fn run_connection(ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>, encoding: GatewayEncoding, write_rx: &mut futures_channel::mpsc::UnboundedReceiver<GatewayPayload>, read_tx: &mpsc::Sender<ShardPayload>, session: &Arc<GatewaySession>, connection: u32) -> ConnectionEnd {
     // Split the websocket stream into a write sink and a read stream
-    let (write, read) = ws_stream.split();
+    let (mut write, mut read) = ws_stream.split();
 
     // Send every message from the read stream to the receive_message method
-    let read_thread = read.for_each(|message| async {
-        receive_message(message, read_tx.clone()).await;
-    });
+    // until the websocket is closed, then return the close code
+    let read_thread = async {
+        let mut zlib_stream = ZlibStream::new();
+        while let Some(message) = read.next().await {
+            match message {
+                Ok(Message::Close(frame)) => {
+                    debug!("Received a close frame: {:?}", frame);
+                    return frame.map(|frame| GatewayCloseCode::from(u16::from(frame.code)));
+                },
+                Ok(Message::Text(text)) => receive_message(text.as_bytes(), encoding, read_tx.clone(), session.clone()).await,
+                // Binary messages are compressed and can be split across multiple frames
+                Ok(Message::Binary(data)) => match zlib_stream.push(&data) {
+                    Ok(Some(message)) => receive_message(&message, encoding, read_tx.clone(), session.clone()).await,
+                    Ok(None) => trace!("Received a partial message from the gateway"),
+                    Err(e) => {
+                        warn!("Failed to decompress a message from the gateway: {}", e);
+                        break;
+                    }
+                },
+                Ok(_) => {},
+                Err(e) => {
+                    warn!("Failed to read from the websocket: {}", e);
+                    break;
+                }
+            }
+        }
+        None
+    };
+
+    // Queue every payload from the UnboundedReceiver, then encode and send them to the write sink
+    // as fast as the gateway's command limit allows, until all senders are dropped
+    let write_thread = async {
+        let mut queue = CommandQueue::new();
+        loop {
+            let delay = match queue.next() {
+                NextCommand::Send(payload) => {
+                    let message = match encode_payload(&payload, encoding) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            error!("Failed to encode the payload: {}", e);
+                            continue;
+                        }
+                    };
+                    write.send(message).await?;
+                    continue;
+                },
+                NextCommand::Wait(delay) => Some(delay),
+                NextCommand::Empty => None
+            };
 
-    // Send every message from the UnboundedReceiver to the write sink
-    let write_thread = write_rx.map(Ok).forward(write);
+            // Wait for a new payload, or until the queued ones can be sent
+            let received = match delay {
+                Some(delay) => tokio::time::timeout(delay, write_rx.next()).await.ok(),
+                None => Some(write_rx.next().await)
+            };
+            match received {
+                Some(Some(payload)) => queue.push(payload),
+                Some(None) => return Ok::<(), tungstenite::Error>(()),
+                None => {}
+            }
+
+            // Take everything else that's waiting so heartbeats can skip ahead of it
+            while let Ok(Some(payload)) = write_rx.try_next() {
+                queue.push(payload);
+            }
+        }
+    };
+
+    let reconnect = session.reconnect_requested(connection);
 
     // Pin the values on the stack
-    pin_mut!(read_thread, write_thread);
+    pin_mut!(read_thread, write_thread, reconnect);
     // wait for any of the threads to finish
-    future::select(read_thread, write_thread).await;
-    debug!("Websocket has been closed");
+    match future::select(future::select(read_thread, write_thread), reconnect).await {
+        future::Either::Left((future::Either::Left((close_code, _)), _)) => ConnectionEnd::Closed(close_code),
+        future::Either::Left((future::Either::Right((result, _)), _)) => {
+            if let Err(e) = result {
+                warn!("Failed to write to the websocket: {}", e);
+                return ConnectionEnd::Closed(None);
+            }
+            ConnectionEnd::Shutdown
+        },
+        future::Either::Right(_) => ConnectionEnd::Reconnect
+    }
+}
+
+/// `write_rx` receives payloads to be sent to the websocket.
+/// Payloads received from the websocket are sent to `read_tx` with the shard id of the session, both are encoded with the encoding in `config`.
+/// When the websocket is closed it will reconnect with an increasing delay, the session is resumed on `Hello`.
+/// Returns an error if the gateway closed the connection with a code that makes reconnecting pointless
+pub async fn connect_to_discord(mut write_rx: futures_channel::mpsc::UnboundedReceiver<GatewayPayload>, read_tx: mpsc::Sender<ShardPayload>, session: Arc<GatewaySession>, gateway_url: String, config: Config) -> Result<(), GatewayCloseCode> {
+    let encoding = config.gateway_encoding;
+    let mut reconnect_delay = MIN_RECONNECT_DELAY;
+    loop {
+        // Sessions are resumed on the url given in READY, new ones use the url the shard manager got from the REST api
+        let base_url = match session.resume_gateway_url() {
+            Some(url) if session.can_resume() => url,
+            _ => gateway_url.clone()
+        };
+
+        match connect_to_websocket(&gateway_url(&base_url, &config)).await {
+            Ok((ws_stream, response)) if response.status() == StatusCode::SWITCHING_PROTOCOLS => {
+                reconnect_delay = MIN_RECONNECT_DELAY;
+                let connection = session.new_connection();
+
+                // Drop anything that was queued for the previous connection, like old heartbeats
+                while let Ok(Some(_)) = write_rx.try_next() {}
+
+                let end = run_connection(ws_stream, encoding, &mut write_rx, &read_tx, &session, connection).await;
+                let counters = session.event_counters();
+                debug!("Events delayed so far: {}, dropped so far: {}", counters.delayed(), counters.dropped());
+
+                match end {
+                    ConnectionEnd::Closed(None) => debug!("Websocket of shard {} has been closed", session.shard_id()),
+                    ConnectionEnd::Closed(Some(close_code)) => {
+                        warn!("Websocket of shard {} has been closed: {}", session.shard_id(), close_code);
+                        match close_code.action() {
+                            GatewayCloseAction::Resume => {},
+                            GatewayCloseAction::Reconnect => session.invalidate(),
+                            GatewayCloseAction::Fatal => return Err(close_code)
+                        }
+                    },
+                    ConnectionEnd::Reconnect => {
+                        // Reconnects asked for by the gateway don't need to wait
+                        debug!("Reconnecting to the gateway");
+                        continue;
+                    },
+                    ConnectionEnd::Shutdown => {
+                        info!("All write senders have been dropped");
+                        return Ok(());
+                    }
+                }
+            },
+            Ok((_, response)) => error!("Failed to connect to the websocket! {:?}", response),
+            Err(e) => error!("Failed to connect to the websocket! {}", e)
+        }
+
+        info!("Reconnecting to the gateway in {:?}", reconnect_delay);
+        tokio::time::sleep(reconnect_delay).await;
+        reconnect_delay = cmp::min(reconnect_delay * 2, MAX_RECONNECT_DELAY);
+    }
 }
@@ -0,0 +1,167 @@
+use std::{collections::VecDeque, time::Duration};
+
+use tokio::time::Instant;
+
+use super::data::gateway::{GatewayOpCodes, GatewayPayload};
+
+/// The number of commands the gateway allows per connection in `COMMAND_WINDOW`
+const COMMAND_LIMIT: usize = 120;
+const COMMAND_WINDOW: Duration = Duration::from_secs(60);
+/// Part of the budget only heartbeats can use, so they're never held back by other commands
+const HEARTBEAT_RESERVE: usize = 4;
+
+/// The order queued commands are sent in, from the most to the least important
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum CommandPriority {
+    /// heartbeats keep the connection alive
+    Heartbeat,
+    /// identify and resume start the session
+    Session,
+    /// presence updates, member requests and everything else
+    Other
+}
+
+impl CommandPriority {
+    fn of(payload: &GatewayPayload) -> Self {
+        match payload.op {
+            GatewayOpCodes::Heartbeat => CommandPriority::Heartbeat,
+            GatewayOpCodes::Identify | GatewayOpCodes::Resume => CommandPriority::Session,
+            _ => CommandPriority::Other
+        }
+    }
+
+    /// How many commands can be sent in `COMMAND_WINDOW` before commands with this priority have to wait
+    fn limit(self) -> usize {
+        match self {
+            CommandPriority::Heartbeat => COMMAND_LIMIT,
+            _ => COMMAND_LIMIT - HEARTBEAT_RESERVE
+        }
+    }
+}
+
+/// What should be done with the queue next
+pub enum NextCommand {
+    /// the payload can be sent right now
+    Send(Box<GatewayPayload>),
+    /// nothing can be sent until the delay is over
+    Wait(Duration),
+    /// there's nothing queued
+    Empty
+}
+
+/// Holds the commands waiting to be sent to a single websocket connection,
+/// so the gateway's send limit isn't exceeded and heartbeats are sent before anything else
+pub struct CommandQueue {
+    /// queued payloads, one queue per priority
+    queued: [VecDeque<GatewayPayload>; 3],
+    /// when the commands in the current window were sent, oldest first
+    sent: VecDeque<Instant>
+}
+
+impl CommandQueue {
+    pub fn new() -> Self {
+        CommandQueue {
+            queued: Default::default(),
+            sent: VecDeque::with_capacity(COMMAND_LIMIT)
+        }
+    }
+
+    pub fn push(&mut self, payload: GatewayPayload) {
+        let priority = CommandPriority::of(&payload);
+        if priority == CommandPriority::Other && self.sent.len() >= priority.limit() {
+            debug!("Gateway command limit reached, delaying {:?}", payload.op);
+        }
+        self.queued[priority as usize].push_back(payload);
+    }
+
+    /// Takes the most important payload if it can be sent now, it's counted as sent
+    pub fn next(&mut self) -> NextCommand {
+        let now = Instant::now();
+        while self.sent.front().is_some_and(|sent| now.duration_since(*sent) >= COMMAND_WINDOW) {
+            self.sent.pop_front();
+        }
+
+        let queue = match self.queued.iter_mut().find(|queue| !queue.is_empty()) {
+            Some(q) => q,
+            None => return NextCommand::Empty
+        };
+        let priority = CommandPriority::of(&queue[0]);
+
+        // Wait until enough commands are out of the window to get below the limit
+        let limit = priority.limit();
+        if self.sent.len() >= limit {
+            let frees_up = self.sent[self.sent.len() - limit] + COMMAND_WINDOW;
+            return NextCommand::Wait(frees_up.saturating_duration_since(now));
+        }
+
+        trace!("Sending a gateway command with priority {:?}", priority);
+        self.sent.push_back(now);
+        NextCommand::Send(Box::new(queue.pop_front().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time;
+
+    use super::*;
+
+    fn payload(op: GatewayOpCodes) -> GatewayPayload {
+        GatewayPayload { op, d: None, s: None, t: None }
+    }
+
+    /// Takes the next payload, failing if nothing can be sent right now
+    fn sent_op(queue: &mut CommandQueue) -> GatewayOpCodes {
+        match queue.next() {
+            NextCommand::Send(payload) => payload.op,
+            NextCommand::Wait(delay) => panic!("had to wait {:?}", delay),
+            NextCommand::Empty => panic!("nothing was queued")
+        }
+    }
+
+    #[test]
+    fn sends_by_priority() {
+        let mut queue = CommandQueue::new();
+        queue.push(payload(GatewayOpCodes::RequestGuildMembers));
+        queue.push(payload(GatewayOpCodes::Identify));
+        queue.push(payload(GatewayOpCodes::PresenceUpdate));
+        queue.push(payload(GatewayOpCodes::Heartbeat));
+
+        assert_eq!(sent_op(&mut queue), GatewayOpCodes::Heartbeat);
+        assert_eq!(sent_op(&mut queue), GatewayOpCodes::Identify);
+        // Commands with the same priority keep their order
+        assert_eq!(sent_op(&mut queue), GatewayOpCodes::RequestGuildMembers);
+        assert_eq!(sent_op(&mut queue), GatewayOpCodes::PresenceUpdate);
+        assert!(matches!(queue.next(), NextCommand::Empty));
+    }
+
+    #[tokio::test]
+    async fn keeps_heartbeats_in_the_budget() {
+        time::pause();
+        let mut queue = CommandQueue::new();
+        for _ in 0..COMMAND_LIMIT {
+            queue.push(payload(GatewayOpCodes::PresenceUpdate));
+        }
+        for _ in 0..COMMAND_LIMIT - HEARTBEAT_RESERVE {
+            assert_eq!(sent_op(&mut queue), GatewayOpCodes::PresenceUpdate);
+        }
+
+        // The rest of the budget is only for heartbeats
+        time::advance(Duration::from_secs(10)).await;
+        assert!(matches!(queue.next(), NextCommand::Wait(delay) if delay == Duration::from_secs(50)));
+        for _ in 0..HEARTBEAT_RESERVE {
+            queue.push(payload(GatewayOpCodes::Heartbeat));
+            assert_eq!(sent_op(&mut queue), GatewayOpCodes::Heartbeat);
+        }
+        queue.push(payload(GatewayOpCodes::Heartbeat));
+        assert!(matches!(queue.next(), NextCommand::Wait(delay) if delay == Duration::from_secs(50)));
+
+        // Commands that left the window free up the budget
+        time::advance(Duration::from_secs(50)).await;
+        assert_eq!(sent_op(&mut queue), GatewayOpCodes::Heartbeat);
+        for _ in 0..HEARTBEAT_RESERVE {
+            assert_eq!(sent_op(&mut queue), GatewayOpCodes::PresenceUpdate);
+        }
+        assert!(matches!(queue.next(), NextCommand::Empty));
+    }
+}
@@ -3,43 +3,45 @@ use serde_repr::*;
 use serde::{Deserialize, Serialize};
 
 use self::user::UserInfo;
+pub use self::{snowflake::Snowflake, timestamp::Timestamp};
 
-// TODO: Maybe convert ids from Strings to a custom Snowflake type
 // TODO: Split into multiple files
 
 pub mod gateway;
+pub mod event;
 pub mod user;
 pub mod guild;
 pub mod channel;
 pub mod sendable;
 pub mod receive;
 pub mod message;
+mod snowflake;
+pub mod timestamp;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
-pub struct Snowflake(String);
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PayloadEntryList<T> {
     pub version: i32,
     pub partial: bool,
     pub entries: Vec<T>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReadStateEntry {
     pub mention_count: i32,
-    pub last_pin_timestamp: String,
-    pub last_message_id: String,
-    pub id: String
+    pub last_pin_timestamp: Option<Timestamp>,
+    /// the last message the user has read, `None` if they haven't read anything in the channel
+    pub last_message_id: Option<Snowflake>,
+    /// the id of the channel
+    pub id: Snowflake
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MuteConfig {
     pub selected_time_window: i32,
-    pub end_time: Option<String>
+    pub end_time: Option<Timestamp>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GuildHashInfo {
     version: Option<u8>,
     roles: OmittableHash,
@@ -47,7 +49,7 @@ pub struct GuildHashInfo {
     channels: OmittableHash
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectedAccountInfo {
     pub visibility: u8, 
     pub verified: bool,
@@ -61,7 +63,7 @@ pub struct ConnectedAccountInfo {
     pub access_token: Option<String>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct OmittableHash {
     omitted: Option<bool>,
     hash: Option<String>
@@ -69,7 +71,7 @@ struct OmittableHash {
 
 // TODO: Move the things below to some other file
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Webhook {
     /// The default name of the Webhook
     pub name: String,
@@ -77,20 +79,20 @@ pub struct Webhook {
     #[serde(rename="type")]
     pub webhook_type: i32,
     /// The channel id this Webhook is for
-    pub channel_id: Value, // TODO change to snowflake (:
+    pub channel_id: Snowflake,
     /// The guild id  this Webhook is for
-    pub guild_id: Option<Value>, // TODO change to snowflake (:
+    pub guild_id: Option<Snowflake>,
     /// The user object
     pub user: Option<UserInfo>,
     /// The secure token of the webhook (returned for incoming Webhooks)
     pub token: Option<String>,
     /// The bot/OAuth2 applications that created this Webhook
-    pub application_id: Option<Value>, // TODO change to snowflake (:
+    pub application_id: Option<Snowflake>,
     /// The default avatar of the webhook
     pub avator: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateWebhook {
     /// The name of the Webhook
     pub name: String,
@@ -98,17 +100,17 @@ pub struct CreateWebhook {
     pub avatar: Option<Value>, // TODO change to ImageData
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModifyWebhook {
     /// The default name of the Webhook
     pub name : String,
     /// The image for the default Webhook avatar
     pub avatar: Option<Value>, // TODO change to ImageData
     /// The new channel id this Webhook should be moved to
-    pub channel_id: Value, // TODO change to snowflake (:
+    pub channel_id: Snowflake,
 }
 
-#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq)]
 #[repr(u8)]
 pub enum ResposeResult {
     /// everything is good
@@ -1,19 +1,11 @@
 use serde::{Deserialize, Serialize};
 use serde_repr::*;
 
-use super::{Snowflake, user::UserInfo};
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PrivateChannelEntry {
-    #[serde(rename="type")]
-    channel_type: ChannelTypes,
-    recipient_ids: Vec<String>,
-    last_message_id: String,
-    id: String
-}
+use super::{Snowflake, Timestamp, user::UserInfo};
 
 /// https://discord.com/developers/docs/resources/channel
-#[derive(Debug, Serialize, Deserialize)]
+/// Used for both guild channels and DMs, fields that only one of them has are optional or default to empty
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelInfo {
     /// the id of this channel
     pub id: Snowflake,
@@ -25,6 +17,7 @@ pub struct ChannelInfo {
     /// sorting position of the channel
     pub position: Option<i32>,
     /// explicit permission overwrites for members and roles
+    #[serde(default)]
     pub permission_overwrites: Vec<ChannelPermissionOverwrite>,
     // the name of the channel (2-100 characters)
     pub name: Option<String>,
@@ -41,7 +34,10 @@ pub struct ChannelInfo {
     /// amount of seconds a user has to wait before sending another message (0-21600); bots, as well as users with the permission `manage_messages` or `manage_channel`, are unaffected
     pub rate_limit_per_user: Option<i32>,
     /// the recipients of the DM
+    #[serde(default)]
     pub recipients: Vec<UserInfo>,
+    /// the ids of the recipients of the DM, sent instead of `recipients` in the READY event of user accounts
+    pub recipient_ids: Option<Vec<Snowflake>>,
     /// icon hash
     pub icon: Option<String>,
     /// id of the DM creator
@@ -51,23 +47,23 @@ pub struct ChannelInfo {
     /// id of the parent category for a channel (each parent category can contain up to 50 channels)
     pub parent_id: Option<Snowflake>,
     /// when the last pinned message was pinned. This may be null in events such as GUILD_CREATE when a message is not pinned.
-    last_pin_timestamp: Option<String>
+    pub last_pin_timestamp: Option<Timestamp>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelPermissionOverwrite {
     /// role or user id
-    id: String,
+    pub id: Snowflake,
     /// either 0 (role) or 1 (member)
     #[serde(rename="type")]
-    target_type: u8,
+    pub target_type: u8,
     /// permission bit set
-    allow: String,
+    pub allow: String,
     /// permission bit set
-    deny: String
+    pub deny: String
 }
 
-#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq)]
 #[repr(u8)]
 pub enum ChannelTypes {
     /// a text channel within a server
@@ -122,4 +118,11 @@ bitflags! {
         const MANAGE_WEBHOOKS = 1 << 29;
         const MANAGE_EMOJIS  = 1 << 30;
     }
-}
\ No newline at end of file
+}
+
+impl ChannelPermissionFlags {
+    /// Parses a permission bit set, which discord sends as a string, unknown permissions are ignored
+    pub fn parse(bits: &str) -> Self {
+        Self::from_bits_truncate(bits.parse().unwrap_or(0))
+    }
+}
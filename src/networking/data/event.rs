@@ -0,0 +1,493 @@
+use serde::{Deserialize, Deserializer, Serialize, de::{self, IgnoredAny}};
+use serde_json::Value;
+
+use super::{ConnectedAccountInfo, PayloadEntryList, ReadStateEntry, Snowflake, Timestamp, channel::ChannelInfo, gateway::BufferedData, guild::{ClientStatusInfo, GuildEmojiInfo, GuildInfo, GuildMemberInfo, GuildRoleInfo, PresenceUpdateEventInfo, VoiceState, activity::ActivityInfo}, message::{MessageInfo, embed::EmbedInfo}, user::{UserGuildSettingEntry, UserInfo, UserRelationship, UserSettings}};
+
+/// An event sent in a dispatch payload, decoded based on the event name in `t`
+/// https://discord.com/developers/docs/topics/gateway#commands-and-events-gateway-events
+#[derive(Debug)]
+pub enum DispatchEvent {
+    /// READY
+    Ready(Box<ReadyData>),
+    /// RESUMED
+    Resumed,
+    /// MESSAGE_CREATE
+    MessageCreate(Box<MessageInfo>),
+    /// MESSAGE_UPDATE
+    MessageUpdate(MessageUpdateData),
+    /// MESSAGE_DELETE
+    MessageDelete(MessageDeleteData),
+    /// MESSAGE_DELETE_BULK
+    MessageDeleteBulk(MessageDeleteBulkData),
+    /// MESSAGE_REACTION_ADD
+    MessageReactionAdd(MessageReactionData),
+    /// MESSAGE_REACTION_REMOVE
+    MessageReactionRemove(MessageReactionData),
+    /// MESSAGE_REACTION_REMOVE_ALL
+    MessageReactionRemoveAll(MessageReactionRemoveAllData),
+    /// MESSAGE_REACTION_REMOVE_EMOJI
+    MessageReactionRemoveEmoji(MessageReactionRemoveEmojiData),
+    /// CHANNEL_PINS_UPDATE
+    ChannelPinsUpdate(ChannelPinsUpdateData),
+    /// MESSAGE_ACK
+    MessageAck(MessageAckData),
+    /// RELATIONSHIP_ADD
+    RelationshipAdd(RelationshipAddData),
+    /// RELATIONSHIP_REMOVE
+    RelationshipRemove(RelationshipRemoveData),
+    /// PRESENCE_UPDATE
+    PresenceUpdate(PresenceUpdateData),
+    /// CHANNEL_CREATE
+    ChannelCreate(Box<ChannelInfo>),
+    /// CHANNEL_UPDATE
+    ChannelUpdate(Box<ChannelInfo>),
+    /// CHANNEL_DELETE
+    ChannelDelete(Box<ChannelInfo>),
+    /// CHANNEL_RECIPIENT_ADD
+    ChannelRecipientAdd(ChannelRecipientData),
+    /// CHANNEL_RECIPIENT_REMOVE
+    ChannelRecipientRemove(ChannelRecipientData),
+    /// GUILD_CREATE
+    GuildCreate(Box<GuildInfo>),
+    /// GUILD_UPDATE
+    GuildUpdate(Box<GuildInfo>),
+    /// GUILD_DELETE
+    GuildDelete(UnavailableGuildData),
+    /// GUILD_MEMBER_ADD
+    GuildMemberAdd(GuildMemberAddData),
+    /// GUILD_MEMBER_UPDATE
+    GuildMemberUpdate(GuildMemberUpdateData),
+    /// GUILD_MEMBER_REMOVE
+    GuildMemberRemove(GuildMemberRemoveData),
+    /// GUILD_MEMBERS_CHUNK
+    GuildMembersChunk(GuildMembersChunkData),
+    /// GUILD_ROLE_CREATE
+    GuildRoleCreate(GuildRoleData),
+    /// GUILD_ROLE_UPDATE
+    GuildRoleUpdate(GuildRoleData),
+    /// GUILD_ROLE_DELETE
+    GuildRoleDelete(GuildRoleDeleteData),
+    /// GUILD_EMOJIS_UPDATE
+    GuildEmojisUpdate(GuildEmojisUpdateData),
+    /// VOICE_STATE_UPDATE
+    VoiceStateUpdate(Box<VoiceState>),
+    /// USER_UPDATE
+    UserUpdate(Box<UserInfo>),
+    /// USER_SETTINGS_UPDATE
+    UserSettingsUpdate(UserSettingsUpdateData),
+    /// an event that isn't supported or couldn't be parsed, with its data as it was received
+    Unknown { name: String, raw: Value }
+}
+
+impl DispatchEvent {
+    /// Deserializes the data of a dispatch payload as the event called `name`.
+    /// Events that aren't supported become `Unknown`, the payload decoder also turns events that fail to parse into `Unknown`
+    pub fn deserialize_named<'de, D: Deserializer<'de>>(name: &str, deserializer: D) -> Result<DispatchEvent, D::Error> {
+        Ok(match name {
+            "READY" => {
+                let mut data = ReadyData::deserialize(deserializer)?;
+                data.merge_members();
+                DispatchEvent::Ready(Box::new(data))
+            },
+            "RESUMED" => {
+                IgnoredAny::deserialize(deserializer)?;
+                DispatchEvent::Resumed
+            },
+            "MESSAGE_CREATE" => DispatchEvent::MessageCreate(Deserialize::deserialize(deserializer)?),
+            "MESSAGE_UPDATE" => DispatchEvent::MessageUpdate(Deserialize::deserialize(deserializer)?),
+            "MESSAGE_DELETE" => DispatchEvent::MessageDelete(Deserialize::deserialize(deserializer)?),
+            "MESSAGE_DELETE_BULK" => DispatchEvent::MessageDeleteBulk(Deserialize::deserialize(deserializer)?),
+            "MESSAGE_REACTION_ADD" => DispatchEvent::MessageReactionAdd(Deserialize::deserialize(deserializer)?),
+            "MESSAGE_REACTION_REMOVE" => DispatchEvent::MessageReactionRemove(Deserialize::deserialize(deserializer)?),
+            "MESSAGE_REACTION_REMOVE_ALL" => DispatchEvent::MessageReactionRemoveAll(Deserialize::deserialize(deserializer)?),
+            "MESSAGE_REACTION_REMOVE_EMOJI" => DispatchEvent::MessageReactionRemoveEmoji(Deserialize::deserialize(deserializer)?),
+            "CHANNEL_PINS_UPDATE" => DispatchEvent::ChannelPinsUpdate(Deserialize::deserialize(deserializer)?),
+            "MESSAGE_ACK" => DispatchEvent::MessageAck(Deserialize::deserialize(deserializer)?),
+            "RELATIONSHIP_ADD" => DispatchEvent::RelationshipAdd(Deserialize::deserialize(deserializer)?),
+            "RELATIONSHIP_REMOVE" => DispatchEvent::RelationshipRemove(Deserialize::deserialize(deserializer)?),
+            "PRESENCE_UPDATE" => DispatchEvent::PresenceUpdate(Deserialize::deserialize(deserializer)?),
+            "CHANNEL_CREATE" => DispatchEvent::ChannelCreate(Deserialize::deserialize(deserializer)?),
+            "CHANNEL_UPDATE" => DispatchEvent::ChannelUpdate(Deserialize::deserialize(deserializer)?),
+            "CHANNEL_DELETE" => DispatchEvent::ChannelDelete(Deserialize::deserialize(deserializer)?),
+            "CHANNEL_RECIPIENT_ADD" => DispatchEvent::ChannelRecipientAdd(Deserialize::deserialize(deserializer)?),
+            "CHANNEL_RECIPIENT_REMOVE" => DispatchEvent::ChannelRecipientRemove(Deserialize::deserialize(deserializer)?),
+            "GUILD_CREATE" => DispatchEvent::GuildCreate(Deserialize::deserialize(deserializer)?),
+            "GUILD_UPDATE" => DispatchEvent::GuildUpdate(Deserialize::deserialize(deserializer)?),
+            "GUILD_DELETE" => DispatchEvent::GuildDelete(Deserialize::deserialize(deserializer)?),
+            "GUILD_MEMBER_ADD" => DispatchEvent::GuildMemberAdd(Deserialize::deserialize(deserializer)?),
+            "GUILD_MEMBER_UPDATE" => DispatchEvent::GuildMemberUpdate(Deserialize::deserialize(deserializer)?),
+            "GUILD_MEMBER_REMOVE" => DispatchEvent::GuildMemberRemove(Deserialize::deserialize(deserializer)?),
+            "GUILD_MEMBERS_CHUNK" => DispatchEvent::GuildMembersChunk(Deserialize::deserialize(deserializer)?),
+            "GUILD_ROLE_CREATE" => DispatchEvent::GuildRoleCreate(Deserialize::deserialize(deserializer)?),
+            "GUILD_ROLE_UPDATE" => DispatchEvent::GuildRoleUpdate(Deserialize::deserialize(deserializer)?),
+            "GUILD_ROLE_DELETE" => DispatchEvent::GuildRoleDelete(Deserialize::deserialize(deserializer)?),
+            "GUILD_EMOJIS_UPDATE" => DispatchEvent::GuildEmojisUpdate(Deserialize::deserialize(deserializer)?),
+            "VOICE_STATE_UPDATE" => DispatchEvent::VoiceStateUpdate(Deserialize::deserialize(deserializer)?),
+            "USER_UPDATE" => DispatchEvent::UserUpdate(Deserialize::deserialize(deserializer)?),
+            "USER_SETTINGS_UPDATE" => DispatchEvent::UserSettingsUpdate(Deserialize::deserialize(deserializer)?),
+            _ => DispatchEvent::Unknown { name: name.to_string(), raw: Value::deserialize(deserializer)? }
+        })
+    }
+
+    /// The session in a READY event, even if the rest of it couldn't be decoded
+    pub fn ready_session(&self) -> Option<ReadySession> {
+        match self {
+            DispatchEvent::Ready(ready) => Some(ReadySession {
+                session_id: ready.session_id.clone(),
+                resume_gateway_url: ready.resume_gateway_url.clone()
+            }),
+            DispatchEvent::Unknown { name, raw } if name == "READY" => ReadySession::deserialize(raw).ok(),
+            _ => None
+        }
+    }
+}
+
+/// https://discord.com/developers/docs/topics/gateway#ready
+/// Most fields are only sent to user accounts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadyData {
+    pub v: u8,
+    #[serde(default)]
+    pub users: Vec<UserInfo>,
+    pub user_settings: Option<UserSettings>,
+    pub user_guild_settings: Option<PayloadEntryList<UserGuildSettingEntry>>,
+    pub user: UserInfo,
+    #[serde(default)]
+    pub tutorial: Value, // TODO
+    pub session_id: String,
+    /// the url to use when resuming this session
+    pub resume_gateway_url: Option<String>,
+    #[serde(default)]
+    pub relationships: Vec<UserRelationship>,
+    pub read_state: Option<PayloadEntryList<ReadStateEntry>>,
+    #[serde(default)]
+    pub private_channels: Vec<ChannelInfo>,
+    /// the members of the current user in every guild, in the same order as `guilds`.
+    /// They're moved to the members of the guilds when READY is decoded
+    #[serde(default)]
+    pub merged_members: Vec<Vec<GuildMemberInfo>>,
+    pub guilds: Vec<ReadyGuild>,
+    #[serde(default)]
+    pub guild_join_requests: Value, // TODO
+    #[serde(default)]
+    pub guild_experiments: Value,   // TODO
+    #[serde(default)]
+    pub geo_ordered_rtc_regions: Vec<String>,
+    pub friend_suggestion_count: Option<i32>,
+    #[serde(default)]
+    pub experiments: Value, // TODO
+    pub country_code: Option<String>,
+    #[serde(default)]
+    pub consents: Value, // TODO
+    #[serde(default)]
+    pub connected_accounts: Vec<ConnectedAccountInfo>,
+    pub analytics_token: Option<String>,
+    #[serde(default)]
+    pub _trace: Value // TODO
+}
+
+/// The fields of READY needed to resume the session
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReadySession {
+    pub session_id: String,
+    pub resume_gateway_url: Option<String>
+}
+
+/// A guild in READY. Bots only get the ids of their guilds, which are sent in GUILD_CREATE events after READY
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ReadyGuild {
+    Available(Box<GuildInfo>),
+    Unavailable(UnavailableGuildData)
+}
+
+impl<'de> Deserialize<'de> for ReadyGuild {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Unavailable guilds only have an id, so a guild with a name is decoded as a full guild and fails if it isn't one
+        let value = Value::deserialize(deserializer)?;
+        let guild = match value.get("name") {
+            Some(_) => GuildInfo::deserialize(BufferedData(&value)).map(|guild| ReadyGuild::Available(Box::new(guild))),
+            None => UnavailableGuildData::deserialize(BufferedData(&value)).map(ReadyGuild::Unavailable)
+        };
+        guild.map_err(de::Error::custom)
+    }
+}
+
+impl ReadyData {
+    /// The guilds that were sent with all of their data
+    pub fn available_guilds(&self) -> impl Iterator<Item = &GuildInfo> {
+        self.guilds.iter().filter_map(|guild| match guild {
+            ReadyGuild::Available(guild) => Some(&**guild),
+            ReadyGuild::Unavailable(_) => None
+        })
+    }
+
+    /// Adds the members in `merged_members` to their guilds.
+    /// They only have the id of their user, so the user is looked up in `user` and `users`
+    fn merge_members(&mut self) {
+        let merged_members = std::mem::take(&mut self.merged_members);
+        let (current_user, users) = (&self.user, &self.users);
+        for (guild, members) in self.guilds.iter_mut().zip(merged_members) {
+            let guild = match guild {
+                ReadyGuild::Available(guild) => guild,
+                ReadyGuild::Unavailable(_) => continue
+            };
+            let guild_members = guild.members.get_or_insert_with(Vec::new);
+            for mut member in members {
+                if member.user.is_none() {
+                    member.user = member.user_id.and_then(|id| {
+                        std::iter::once(current_user).chain(users).find(|user| user.id == id).cloned()
+                    });
+                }
+
+                if !guild_members.iter().any(|existing| existing.user_id() == member.user_id()) {
+                    guild_members.push(member);
+                }
+            }
+        }
+    }
+}
+
+/// https://discord.com/developers/docs/topics/gateway#message-update
+/// Only the id and channel id are always sent, the other fields are only there if they changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageUpdateData {
+    /// id of the message
+    pub id: Snowflake,
+    /// id of the channel the message was sent in
+    pub channel_id: Snowflake,
+    /// id of the guild the message was sent in
+    pub guild_id: Option<Snowflake>,
+    /// the new contents of the message
+    pub content: Option<String>,
+    /// when this message was edited
+    pub edited_timestamp: Option<Timestamp>,
+    /// whether this message is pinned
+    pub pinned: Option<bool>,
+    /// the new embedded content
+    pub embeds: Option<Vec<EmbedInfo>>
+}
+
+/// https://discord.com/developers/docs/topics/gateway#message-delete
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageDeleteData {
+    /// the id of the message
+    pub id: Snowflake,
+    /// the id of the channel
+    pub channel_id: Snowflake,
+    /// the id of the guild
+    pub guild_id: Option<Snowflake>
+}
+
+/// https://discord.com/developers/docs/topics/gateway#message-delete-bulk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageDeleteBulkData {
+    /// the ids of the messages
+    pub ids: Vec<Snowflake>,
+    /// the id of the channel
+    pub channel_id: Snowflake,
+    /// the id of the guild
+    pub guild_id: Option<Snowflake>
+}
+
+/// https://discord.com/developers/docs/topics/gateway#message-reaction-add
+/// Also used for MESSAGE_REACTION_REMOVE, which doesn't have a member
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageReactionData {
+    /// the id of the user
+    pub user_id: Snowflake,
+    /// the id of the channel
+    pub channel_id: Snowflake,
+    /// the id of the message
+    pub message_id: Snowflake,
+    /// the id of the guild
+    pub guild_id: Option<Snowflake>,
+    /// the member who reacted if this happened in a guild
+    pub member: Option<GuildMemberInfo>,
+    /// the emoji used to react, only the id, name and animated fields are sent
+    pub emoji: GuildEmojiInfo
+}
+
+/// https://discord.com/developers/docs/topics/gateway#message-reaction-remove-all
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageReactionRemoveAllData {
+    /// the id of the channel
+    pub channel_id: Snowflake,
+    /// the id of the message
+    pub message_id: Snowflake,
+    /// the id of the guild
+    pub guild_id: Option<Snowflake>
+}
+
+/// https://discord.com/developers/docs/topics/gateway#message-reaction-remove-emoji
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageReactionRemoveEmojiData {
+    /// the id of the channel
+    pub channel_id: Snowflake,
+    /// the id of the guild
+    pub guild_id: Option<Snowflake>,
+    /// the id of the message
+    pub message_id: Snowflake,
+    /// the emoji that was removed
+    pub emoji: GuildEmojiInfo
+}
+
+/// https://discord.com/developers/docs/topics/gateway#channel-pins-update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelPinsUpdateData {
+    /// the id of the guild
+    pub guild_id: Option<Snowflake>,
+    /// the id of the channel
+    pub channel_id: Snowflake,
+    /// the time at which the most recent pinned message was pinned
+    pub last_pin_timestamp: Option<Timestamp>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageAckData {
+    pub version: u8,
+    pub message_id: Snowflake,
+    pub channel_id: Snowflake
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationshipAddData {
+    pub user: UserInfo,
+    #[serde(rename="type")]
+    pub relationship_type: u8,
+    pub should_notify: Option<bool>,
+    pub nickname: Option<String>,
+    pub id: Snowflake
+}
+
+/// Sent when a friend is removed or a user is unblocked, which isn't documented
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationshipRemoveData {
+    /// the id of the user the relationship was with
+    pub id: Snowflake,
+    #[serde(rename="type")]
+    pub relationship_type: u8
+}
+
+/// https://discord.com/developers/docs/topics/gateway#presence-update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresenceUpdateData {
+    pub user: UserInfo,
+    pub status: String,
+    /// only sent to user accounts
+    #[serde(default)]
+    pub last_modified: Option<u64>,
+    pub client_status: ClientStatusInfo,
+    pub activities: Vec<ActivityInfo>
+}
+
+/// A user that was added to or removed from a group DM
+/// Used for CHANNEL_RECIPIENT_ADD and CHANNEL_RECIPIENT_REMOVE, which aren't documented
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelRecipientData {
+    /// the id of the group DM
+    pub channel_id: Snowflake,
+    /// the user that was added or removed
+    pub user: UserInfo
+}
+
+/// https://discord.com/developers/docs/resources/guild#unavailable-guild-object
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnavailableGuildData {
+    /// guild id
+    pub id: Snowflake,
+    /// true if the guild is unavailable due to an outage, not sent if the user left or was removed from the guild
+    pub unavailable: Option<bool>
+}
+
+/// https://discord.com/developers/docs/topics/gateway#guild-member-add
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuildMemberAddData {
+    /// id of the guild
+    pub guild_id: Snowflake,
+    #[serde(flatten)]
+    pub member: GuildMemberInfo
+}
+
+/// https://discord.com/developers/docs/topics/gateway#guild-member-update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuildMemberUpdateData {
+    /// the id of the guild
+    pub guild_id: Snowflake,
+    /// user role ids
+    pub roles: Vec<Snowflake>,
+    /// the user
+    pub user: UserInfo,
+    /// nickname of the user in the guild
+    pub nick: Option<String>,
+    /// when the user joined the guild
+    pub joined_at: Option<Timestamp>,
+    /// when the user starting boosting the guild
+    pub premium_since: Option<Timestamp>,
+    /// whether the user has not yet passed the guild's Membership Screening requirements
+    pub pending: Option<bool>
+}
+
+/// https://discord.com/developers/docs/topics/gateway#guild-member-remove
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuildMemberRemoveData {
+    /// the id of the guild
+    pub guild_id: Snowflake,
+    /// the user who was removed
+    pub user: UserInfo
+}
+
+/// https://discord.com/developers/docs/topics/gateway#guild-members-chunk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuildMembersChunkData {
+    /// the id of the guild
+    pub guild_id: Snowflake,
+    /// set of guild members
+    pub members: Vec<GuildMemberInfo>,
+    /// the chunk index in the expected chunks for this response
+    pub chunk_index: i32,
+    /// the total number of expected chunks for this response
+    pub chunk_count: i32,
+    /// if passing an invalid id to REQUEST_GUILD_MEMBERS, it will be returned here
+    pub not_found: Option<Vec<Value>>,
+    /// if passing true to REQUEST_GUILD_MEMBERS, presences of the returned members will be here
+    pub presences: Option<Vec<PresenceUpdateEventInfo>>,
+    /// the nonce used in the REQUEST_GUILD_MEMBERS request
+    pub nonce: Option<String>
+}
+
+/// https://discord.com/developers/docs/topics/gateway#guild-role-create
+/// Also used for GUILD_ROLE_UPDATE
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuildRoleData {
+    /// the id of the guild
+    pub guild_id: Snowflake,
+    /// the role created or updated
+    pub role: GuildRoleInfo
+}
+
+/// https://discord.com/developers/docs/topics/gateway#guild-role-delete
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuildRoleDeleteData {
+    /// id of the guild
+    pub guild_id: Snowflake,
+    /// id of the role
+    pub role_id: Snowflake
+}
+
+/// https://discord.com/developers/docs/topics/gateway#guild-emojis-update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuildEmojisUpdateData {
+    /// id of the guild
+    pub guild_id: Snowflake,
+    /// array of emojis
+    pub emojis: Vec<GuildEmojiInfo>
+}
+
+/// The settings that changed, sent to user accounts which isn't documented.
+/// Only the settings that are used are decoded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserSettingsUpdateData {
+    pub timezone_offset: Option<i32>,
+    pub status: Option<String>
+}
\ No newline at end of file
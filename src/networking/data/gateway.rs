@@ -1,11 +1,14 @@
-use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, de::{self, DeserializeOwned, DeserializeSeed, IgnoredAny, IntoDeserializer, MapAccess, Visitor, value::{MapDeserializer, SeqDeserializer}}};
 use serde_json::Value;
 use serde_repr::*;
 
-use super::{ConnectedAccountInfo, PayloadEntryList, ReadStateEntry, Snowflake, channel::{ChannelTypes, PrivateChannelEntry}, guild::{ClientStatusInfo, GuildInfo, activity::ActivityInfo}, message::MessageInfo, user::{UserGuildSettingEntry, UserInfo, UserRelationship, UserSettings}};
+use super::event::DispatchEvent;
+use crate::networking::etf;
 
 /// https://discord.com/developers/docs/topics/gateway
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize)]
 pub struct GatewayPayload {
     /// opcode for the payload
     pub op: GatewayOpCodes,
@@ -17,70 +20,223 @@ pub struct GatewayPayload {
     pub t: Option<String>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Deserialize)]
+struct HelloInfo {
+    heartbeat_interval: u32
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum PayloadField {
+    Op,
+    D,
+    S,
+    T,
+    #[serde(other)]
+    Other
+}
+
+struct PayloadVisitor;
+
+/// Decodes the `d` field of a payload that isn't a dispatch as the type its opcode says it is
+struct PayloadDataSeed<'a> {
+    op: &'a GatewayOpCodes
+}
+
+/// Deserializes data that was kept as a json value, because it was received before the fields saying what it is or is the data of an event.
+/// Integers are converted into strings where strings are expected, like the etf deserializer does
+pub(super) struct BufferedData<'a>(pub &'a Value);
+
+impl GatewayPayload {
+    /// Decodes a payload received from the gateway.
+    /// A dispatch whose event fails to decode is still returned, as an `Unknown` event with its data as it was received
+    pub fn decode(message: &[u8], encoding: GatewayEncoding) -> Result<GatewayPayload, Box<dyn Error + Send + Sync>> {
+        encoding.decode(message)
+    }
+}
+
+impl<'de> Deserialize<'de> for GatewayPayload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(PayloadVisitor)
+    }
+}
+
+impl<'de> Visitor<'de> for PayloadVisitor {
+    type Value = GatewayPayload;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a gateway payload")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let (mut op, mut d, mut s, mut t) = (None, None, None, None);
+        let mut buffered = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                PayloadField::Op => op = Some(map.next_value()?),
+                PayloadField::S => s = map.next_value()?,
+                PayloadField::T => t = map.next_value()?,
+                // The data of other payloads is decoded right away if the opcode was already read, which is usually the case for json.
+                // Etf sorts the keys, so `d` comes first and has to be kept until the opcode is read.
+                // Events are always kept, so one that fails to decode can still be returned with its data
+                PayloadField::D => match &op {
+                    Some(op) if *op != GatewayOpCodes::Dispatch => {
+                        d = map.next_value_seed(PayloadDataSeed { op })?;
+                    },
+                    _ => buffered = Some(map.next_value::<Value>()?)
+                },
+                PayloadField::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let op = op.ok_or_else(|| de::Error::missing_field("op"))?;
+        if let Some(data) = buffered {
+            d = match (&op, t.as_deref()) {
+                (GatewayOpCodes::Dispatch, Some(name)) => {
+                    let result = DispatchEvent::deserialize_named(name, BufferedData(&data));
+                    let event = match result {
+                        Ok(event) => event,
+                        Err(e) => {
+                            warn!("Failed to parse the {} event: {}", name, e);
+                            DispatchEvent::Unknown { name: name.to_string(), raw: data }
+                        }
+                    };
+                    Some(GatewayPayloadData::Dispatch(Box::new(event)))
+                },
+                _ => PayloadDataSeed { op: &op }.deserialize(BufferedData(&data)).map_err(de::Error::custom)?
+            };
+        }
+        Ok(GatewayPayload { op, d, s, t })
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for PayloadDataSeed<'_> {
+    type Value = Option<GatewayPayloadData>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        Ok(match self.op {
+            GatewayOpCodes::Hello => {
+                let hello = HelloInfo::deserialize(deserializer)?;
+                Some(GatewayPayloadData::HelloData { heartbeat_interval: hello.heartbeat_interval })
+            },
+            GatewayOpCodes::InvalidSession => Some(GatewayPayloadData::InvalidSessionData(bool::deserialize(deserializer)?)),
+            // The other payloads sent by the gateway don't have any data that's used
+            _ => {
+                IgnoredAny::deserialize(deserializer)?;
+                None
+            }
+        })
+    }
+}
+
+impl<'de> Deserializer<'de> for BufferedData<'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Array(values) => visitor.visit_seq(SeqDeserializer::new(values.iter().map(BufferedData))),
+            Value::Object(fields) => visitor.visit_map(MapDeserializer::new(fields.iter().map(|(key, value)| (key.as_str(), BufferedData(value))))),
+            value => value.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Number(number) => visitor.visit_string(number.to_string()),
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, serde_json::Error> for BufferedData<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// The data of a payload, received ones are decoded by `GatewayPayload::decode`
+#[derive(Debug, Serialize)]
 #[serde(untagged)]
 pub enum GatewayPayloadData {
-    HelloData { heartbeat_interval: u32, _trace: Value },
-    ReadyData {
-        v: u8,
-        users: Vec<UserInfo>,
-        user_settings: UserSettings,
-        user_guild_settings: PayloadEntryList<UserGuildSettingEntry>,
-        user: UserInfo,
-        tutorial: Value, // TODO
-        session_id: String,
-        relationships: Vec<UserRelationship>,
-        read_state: PayloadEntryList<ReadStateEntry>,
-        private_channels: Vec<PrivateChannelEntry>,
-        merged_members: Value, // TODO: Convert this thing to a struct
-        guilds: Vec<GuildInfo>,
-        guild_join_requests: Value, // TODO
-        guild_experiments: Value,   // TODO
-        geo_ordered_rtc_regions: Vec<String>,
-        friend_suggestion_count: Option<i32>,
-        experiments: Value, // TODO
-        country_code: Option<String>,
-        consents: Value, // TODO
-        connected_accounts: Vec<ConnectedAccountInfo>,
-        analytics_token: String,
-        _trace: Value // TODO
-    },
+    HelloData { heartbeat_interval: u32 },
     IdentifyMessageData {
         token: String,
         capabilities: i32,
         properties: IdentifyProperties
     },
-    MessageCreateData {
-        #[serde(flatten)]
-        message_data: MessageInfo
-    },
-    RelationshipAddData {
-        user: UserInfo,
-        #[serde(rename="type")]
-        relationship_type: u8,
-        should_notify: Option<bool>,
-        nickname: Option<String>,
-        id: Snowflake
-    },
-    PresenceUpdateData {
-        user: UserInfo,
-        status: String,
-        last_modified: u64,
-        client_status: ClientStatusInfo,
-        activities: Vec<ActivityInfo>
+    BotIdentifyData {
+        token: String,
+        intents: GatewayIntents,
+        /// `[shard_id, num_shards]`
+        shard: [u32; 2],
+        /// number of members above which offline members aren't sent in a guild's member list
+        large_threshold: u8,
+        properties: BotIdentifyProperties
     },
-    ChannelCreateData {
-        #[serde(rename="type")]
-        channel_type: ChannelTypes,
-        recipients: Vec<UserInfo>,
-        last_message_id: Option<Snowflake>,
-        id: Snowflake
+    ResumeData {
+        token: String,
+        session_id: String,
+        seq: i32
     },
-    MessageAckData {
-        version: u8,
-        message_id: Snowflake,
-        channel_id: Snowflake
-    }
+    /// whether the session can be resumed
+    InvalidSessionData(bool),
+    /// the last sequence number received
+    HeartbeatData(i32),
+    /// an event sent by the gateway, only received
+    #[serde(skip_serializing)]
+    Dispatch(Box<DispatchEvent>)
+}
+
+/// https://discord.com/developers/docs/topics/gateway#get-gateway-bot
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GatewayInfo {
+    /// the url to connect to the gateway with
+    pub url: String,
+    /// the recommended number of shards, only sent to bots
+    pub shards: Option<u32>,
+    /// information on the current session start limit, only sent to bots
+    pub session_start_limit: Option<SessionStartLimit>
+}
+
+/// https://discord.com/developers/docs/topics/gateway#session-start-limit-object
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionStartLimit {
+    /// the total number of session starts the current user is allowed
+    pub total: u32,
+    /// the remaining number of session starts the current user is allowed
+    pub remaining: u32,
+    /// the number of milliseconds after which the limit resets
+    pub reset_after: u64,
+    /// the number of identify requests allowed per 5 seconds
+    pub max_concurrency: u32
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -90,6 +246,45 @@ pub struct IdentifyProperties {
     pub browser_user_agent: String
 }
 
+/// https://discord.com/developers/docs/topics/gateway#identify-identify-connection-properties
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BotIdentifyProperties {
+    /// the operating system
+    #[serde(rename="$os")]
+    pub os: String,
+    /// the library name
+    #[serde(rename="$browser")]
+    pub browser: String,
+    /// the library name
+    #[serde(rename="$device")]
+    pub device: String
+}
+
+bitflags! {
+    /// https://discord.com/developers/docs/topics/gateway#gateway-intents
+    #[derive(Serialize, Deserialize)]
+    #[serde(transparent)]
+    pub struct GatewayIntents: u64 {
+        const GUILDS = 1 << 0;
+        /// privileged, has to be enabled for the bot
+        const GUILD_MEMBERS = 1 << 1;
+        const GUILD_BANS = 1 << 2;
+        const GUILD_EMOJIS = 1 << 3;
+        const GUILD_INTEGRATIONS = 1 << 4;
+        const GUILD_WEBHOOKS = 1 << 5;
+        const GUILD_INVITES = 1 << 6;
+        const GUILD_VOICE_STATES = 1 << 7;
+        /// privileged, has to be enabled for the bot
+        const GUILD_PRESENCES = 1 << 8;
+        const GUILD_MESSAGES = 1 << 9;
+        const GUILD_MESSAGE_REACTIONS = 1 << 10;
+        const GUILD_MESSAGE_TYPING = 1 << 11;
+        const DIRECT_MESSAGES = 1 << 12;
+        const DIRECT_MESSAGE_REACTIONS = 1 << 13;
+        const DIRECT_MESSAGE_TYPING = 1 << 14;
+    }
+}
+
 #[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq)]
 #[repr(u8)]
 pub enum GatewayOpCodes {
@@ -104,4 +299,251 @@ pub enum GatewayOpCodes {
     InvalidSession = 9,
     Hello = 10,
     HeartbeatAck = 11
-}
\ No newline at end of file
+}
+/// https://discord.com/developers/docs/topics/opcodes-and-status-codes#gateway-gateway-close-event-codes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GatewayCloseCode {
+    /// we're not sure what went wrong
+    UnknownError,
+    /// an invalid opcode or payload for an opcode was sent
+    UnknownOpcode,
+    /// an invalid payload was sent
+    DecodeError,
+    /// a payload was sent prior to identifying
+    NotAuthenticated,
+    /// the account token sent with the identify payload is incorrect
+    AuthenticationFailed,
+    /// more than one identify payload was sent
+    AlreadyAuthenticated,
+    /// the sequence sent when resuming the session was invalid
+    InvalidSeq,
+    /// payloads are being sent too quickly
+    RateLimited,
+    /// the session timed out
+    SessionTimedOut,
+    /// an invalid shard was sent when identifying
+    InvalidShard,
+    /// the session would have handled too many guilds, sharding is required
+    ShardingRequired,
+    /// an invalid version for the gateway was sent
+    InvalidApiVersion,
+    /// an invalid intent was sent
+    InvalidIntents,
+    /// an intent the account isn't approved for was sent
+    DisallowedIntents,
+    /// any other close code, including the standard websocket ones
+    Other(u16)
+}
+
+/// What the client should do after the gateway closed the connection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GatewayCloseAction {
+    /// reconnect and resume the session
+    Resume,
+    /// reconnect and identify again, the session can't be resumed
+    Reconnect,
+    /// don't reconnect, the same error would happen again
+    Fatal
+}
+
+impl GatewayCloseCode {
+    pub fn action(&self) -> GatewayCloseAction {
+        match self {
+            GatewayCloseCode::InvalidSeq | GatewayCloseCode::SessionTimedOut => GatewayCloseAction::Reconnect,
+            GatewayCloseCode::AuthenticationFailed
+            | GatewayCloseCode::InvalidShard
+            | GatewayCloseCode::ShardingRequired
+            | GatewayCloseCode::InvalidApiVersion
+            | GatewayCloseCode::InvalidIntents
+            | GatewayCloseCode::DisallowedIntents => GatewayCloseAction::Fatal,
+            _ => GatewayCloseAction::Resume
+        }
+    }
+}
+
+impl From<u16> for GatewayCloseCode {
+    fn from(code: u16) -> Self {
+        match code {
+            4000 => GatewayCloseCode::UnknownError,
+            4001 => GatewayCloseCode::UnknownOpcode,
+            4002 => GatewayCloseCode::DecodeError,
+            4003 => GatewayCloseCode::NotAuthenticated,
+            4004 => GatewayCloseCode::AuthenticationFailed,
+            4005 => GatewayCloseCode::AlreadyAuthenticated,
+            4007 => GatewayCloseCode::InvalidSeq,
+            4008 => GatewayCloseCode::RateLimited,
+            4009 => GatewayCloseCode::SessionTimedOut,
+            4010 => GatewayCloseCode::InvalidShard,
+            4011 => GatewayCloseCode::ShardingRequired,
+            4012 => GatewayCloseCode::InvalidApiVersion,
+            4013 => GatewayCloseCode::InvalidIntents,
+            4014 => GatewayCloseCode::DisallowedIntents,
+            other => GatewayCloseCode::Other(other)
+        }
+    }
+}
+
+impl fmt::Display for GatewayCloseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GatewayCloseCode::UnknownError => write!(f, "Unknown gateway error"),
+            GatewayCloseCode::UnknownOpcode => write!(f, "Sent an unknown opcode to the gateway"),
+            GatewayCloseCode::DecodeError => write!(f, "Sent an invalid payload to the gateway"),
+            GatewayCloseCode::NotAuthenticated => write!(f, "Sent a payload before identifying"),
+            GatewayCloseCode::AuthenticationFailed => write!(f, "Authentication failed, the token is invalid"),
+            GatewayCloseCode::AlreadyAuthenticated => write!(f, "Identified more than once"),
+            GatewayCloseCode::InvalidSeq => write!(f, "Resumed with an invalid sequence number"),
+            GatewayCloseCode::RateLimited => write!(f, "Sent payloads to the gateway too quickly"),
+            GatewayCloseCode::SessionTimedOut => write!(f, "The session timed out"),
+            GatewayCloseCode::InvalidShard => write!(f, "Identified with an invalid shard"),
+            GatewayCloseCode::ShardingRequired => write!(f, "Too many guilds, sharding is required"),
+            GatewayCloseCode::InvalidApiVersion => write!(f, "Invalid gateway version"),
+            GatewayCloseCode::InvalidIntents => write!(f, "Identified with invalid intents"),
+            GatewayCloseCode::DisallowedIntents => write!(f, "Identified with intents that aren't enabled for this account"),
+            GatewayCloseCode::Other(code) => write!(f, "The gateway closed the connection with code {}", code)
+        }
+    }
+}
+
+/// The format payloads are sent in over the gateway
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GatewayEncoding {
+    Json,
+    /// Erlang External Term Format, smaller and faster to parse than json
+    Etf
+}
+
+impl GatewayEncoding {
+    /// The value of the `encoding` query parameter in the gateway url
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GatewayEncoding::Json => "json",
+            GatewayEncoding::Etf => "etf"
+        }
+    }
+
+    /// Deserializes a whole message received in this encoding
+    pub fn decode<T: DeserializeOwned>(self, message: &[u8]) -> Result<T, Box<dyn Error + Send + Sync>> {
+        Ok(match self {
+            GatewayEncoding::Json => serde_json::from_slice(message)?,
+            GatewayEncoding::Etf => etf::from_slice(message)?
+        })
+    }
+}
+
+impl FromStr for GatewayEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(GatewayEncoding::Json),
+            "etf" => Ok(GatewayEncoding::Etf),
+            other => Err(format!("Unknown gateway encoding: {}", other))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::networking::data::Snowflake;
+
+    fn atom(name: &str) -> Vec<u8> {
+        let mut term = vec![119, name.len() as u8];
+        term.extend_from_slice(name.as_bytes());
+        term
+    }
+
+    /// Snowflakes are sent as 8 byte big integers
+    fn snowflake(id: u64) -> Vec<u8> {
+        let mut term = vec![110, 8, 0];
+        term.extend_from_slice(&id.to_le_bytes());
+        term
+    }
+
+    /// The header of a map with `len` entries, the gateway sends the keys as atoms in sorted order, so `d` comes first
+    fn map(len: u8) -> Vec<u8> {
+        vec![116, 0, 0, 0, len]
+    }
+
+    #[test]
+    fn decodes_etf_hello() {
+        let frame = [
+            vec![131], map(4),
+            atom("d"), map(1), atom("heartbeat_interval"), vec![98, 0, 0, 0xA1, 0x22],
+            atom("op"), vec![97, 10],
+            atom("s"), atom("nil"),
+            atom("t"), atom("nil")
+        ].concat();
+
+        let payload = GatewayPayload::decode(&frame, GatewayEncoding::Etf).unwrap();
+        assert_eq!(payload.op, GatewayOpCodes::Hello);
+        assert!(matches!(payload.d, Some(GatewayPayloadData::HelloData { heartbeat_interval: 41250 })));
+        assert_eq!(payload.s, None);
+        assert_eq!(payload.t, None);
+    }
+
+    #[test]
+    fn decodes_etf_dispatch() {
+        let frame = [
+            vec![131], map(4),
+            atom("d"), map(3),
+            atom("channel_id"), snowflake(175928847299117063),
+            atom("guild_id"), snowflake(41771983423143937),
+            atom("id"), snowflake(175928847299117064),
+            atom("op"), vec![97, 0],
+            atom("s"), vec![97, 5],
+            atom("t"), atom("MESSAGE_DELETE")
+        ].concat();
+
+        let payload = GatewayPayload::decode(&frame, GatewayEncoding::Etf).unwrap();
+        assert_eq!(payload.s, Some(5));
+        assert_eq!(payload.t.as_deref(), Some("MESSAGE_DELETE"));
+        let delete = match payload.d {
+            Some(GatewayPayloadData::Dispatch(event)) => match *event {
+                DispatchEvent::MessageDelete(delete) => delete,
+                event => panic!("decoded the wrong event: {:?}", event)
+            },
+            d => panic!("decoded the wrong data: {:?}", d)
+        };
+        assert_eq!(delete.id, Snowflake::new(175928847299117064));
+        assert_eq!(delete.channel_id, Snowflake::new(175928847299117063));
+        assert_eq!(delete.guild_id, Some(Snowflake::new(41771983423143937)));
+    }
+
+    #[test]
+    fn decodes_presence_update_without_last_modified() {
+        let message = br#"{"t":"PRESENCE_UPDATE","s":3,"op":0,"d":{"user":{"id":"80351110224678912"},"guild_id":"41771983423143937","status":"idle","client_status":{"desktop":"idle"},"activities":[]}}"#;
+
+        let payload = GatewayPayload::decode(message, GatewayEncoding::Json).unwrap();
+        let presence = match payload.d {
+            Some(GatewayPayloadData::Dispatch(event)) => match *event {
+                DispatchEvent::PresenceUpdate(presence) => presence,
+                event => panic!("decoded the wrong event: {:?}", event)
+            },
+            d => panic!("decoded the wrong data: {:?}", d)
+        };
+        assert_eq!(presence.user.id, Snowflake::new(80351110224678912));
+        assert_eq!(presence.status, "idle");
+        assert_eq!(presence.last_modified, None);
+    }
+
+    #[test]
+    fn keeps_events_that_fail_to_decode() {
+        let message = br#"{"t":"MESSAGE_DELETE","s":4,"op":0,"d":{"channel_id":"175928847299117063"}}"#;
+
+        let payload = GatewayPayload::decode(message, GatewayEncoding::Json).unwrap();
+        assert_eq!(payload.s, Some(4));
+        match payload.d {
+            Some(GatewayPayloadData::Dispatch(event)) => match *event {
+                DispatchEvent::Unknown { name, raw } => {
+                    assert_eq!(name, "MESSAGE_DELETE");
+                    assert_eq!(raw, serde_json::json!({ "channel_id": "175928847299117063" }));
+                },
+                event => panic!("decoded the wrong event: {:?}", event)
+            },
+            d => panic!("decoded the wrong data: {:?}", d)
+        }
+    }
+}
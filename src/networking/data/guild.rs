@@ -3,11 +3,11 @@ use serde_json::Value;
 use serde_repr::*;
 
 use self::activity::ActivityInfo;
-use super::{GuildHashInfo, MuteConfig, Snowflake, channel::ChannelInfo, user::UserInfo};
+use super::{GuildHashInfo, MuteConfig, Snowflake, Timestamp, channel::ChannelInfo, user::UserInfo};
 
 pub mod activity;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GuildInfo {
     /// guild id
     pub id: Snowflake,
@@ -24,19 +24,19 @@ pub struct GuildInfo {
     /// true if the user is the owner of the guild
     pub owner: Option<bool>,
     /// id of owner
-    pub owner_id: String,
+    pub owner_id: Snowflake,
     /// total permissions for the user in the guild (excludes overrides)
     permissions: Option<String>,
     /// voice region id for the guild
     pub region: String,
     /// id of afk channel
-    afk_channel_id: Option<String>,
+    afk_channel_id: Option<Snowflake>,
     /// afk timeout in seconds
     afk_timeout: i32,
     /// true if the server widget is enabled
     widget_enabled: Option<bool>,
     /// the channel id that the widget will generate an invite to, or null if set to no invite
-    widget_channel_id: Option<String>,
+    widget_channel_id: Option<Snowflake>,
     /// verification level required for the guild
     verification_level: GuildVerificationLevel,
     /// default message notifications level <br/>
@@ -55,15 +55,15 @@ pub struct GuildInfo {
     /// `NONE` = 0, `ELEVATED` = 1
     pub mfa_level: u8,
     /// application id of the guild creator if it is bot-created
-    pub application_id: Option<String>,
+    pub application_id: Option<Snowflake>,
     /// the id of the channel where guild notices such as welcome messages and boost events are posted
-    pub system_channel_id: Option<String>,
+    pub system_channel_id: Option<Snowflake>,
     /// [system channel flags](https://discord.com/developers/docs/resources/guild#guild-object-system-channel-flags)
     pub system_channel_flags: u8,
     /// the id of the channel where Community guilds can display rules and/or guidelines
-    pub rules_channel_id: Option<String>,
+    pub rules_channel_id: Option<Snowflake>,
     /// when this guild was joined at
-    pub joined_at: Option<String>,
+    pub joined_at: Option<Timestamp>,
     /// true if this is considered a large guild
     pub large: Option<bool>,
     /// true if this guild is unavailable due to an outage
@@ -109,13 +109,14 @@ pub struct GuildInfo {
     /// Isn't documented but exists in the gateway's ready message
     guild_hashes: Option<GuildHashInfo>,
     /// Isn't documented but exists in the gateway's ready message
+    #[serde(default)]
     threads: Value,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GuildRoleInfo {
     /// role id
-    pub id: Option<String>,
+    pub id: Option<Snowflake>,
     /// role name
     pub name: String,
     /// integer representation of hexadecimal color code
@@ -135,18 +136,20 @@ pub struct GuildRoleInfo {
 }
 
 /// https://discord.com/developers/docs/resources/guild#guild-member-object
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GuildMemberInfo {
     /// the user this guild member represents
     pub user: Option<UserInfo>,
+    /// the id of the user, sent instead of `user` in the `merged_members` of READY which isn't documented
+    pub user_id: Option<Snowflake>,
     /// this users guild nickname
     pub nick: Option<String>,
     /// array of [role](https://discord.com/developers/docs/topics/permissions#role-object) object ids
     pub roles: Vec<Snowflake>,
     /// when the user joined the guild
-    pub joined_at: String,
+    pub joined_at: Timestamp,
     /// when the user started boosting the guild
-    pub premium_since: Option<String>,
+    pub premium_since: Option<Timestamp>,
     /// whether the user is deafened in voice channels
     pub deaf: bool,
     /// whether the user is muted in voice channels
@@ -157,14 +160,21 @@ pub struct GuildMemberInfo {
     pub permission: Option<String>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl GuildMemberInfo {
+    /// The id of the user this member represents, from either `user` or `user_id`
+    pub fn user_id(&self) -> Option<Snowflake> {
+        self.user.as_ref().map(|user| user.id).or(self.user_id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GuildEmojiInfo {
     /// emoji id
     pub id: Option<Snowflake>,
     /// emoji name
     pub name: Option<String>,
     /// roles this emoji is whitelisted to
-    pub roles: Option<Vec<String>>,
+    pub roles: Option<Vec<Snowflake>>,
     /// user that created this emoji
     pub user: Option<UserInfo>,
     /// whether this emoji must be wrapped in colons
@@ -177,25 +187,25 @@ pub struct GuildEmojiInfo {
     pub available: Option<bool>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GuildSettingChannelOverride {
     pub muted: bool,
     pub mute_config: MuteConfig,
     pub message_notifications: i32,
     pub collapsed: bool,
-    pub channel_id: String
+    pub channel_id: Snowflake
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GuildFolderInfo {
     pub name: String, 
     pub id: String, // TODO: Find out if it's actually a string
-    pub guild_ids: Vec<String>,
+    pub guild_ids: Vec<Snowflake>,
     pub color: i32
 }
 
 /// https://discord.com/developers/docs/topics/gateway#presence-update
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PresenceUpdateEventInfo {
     /// the user presence is being updated for
     pub user: Option<UserInfo>,
@@ -209,7 +219,7 @@ pub struct PresenceUpdateEventInfo {
     pub client_status: Option<ClientStatusInfo>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WelcomeScreenInfo {
     /// the server description shown in the welcome screen
     pub description: Option<String>,
@@ -217,7 +227,7 @@ pub struct WelcomeScreenInfo {
     pub welcome_channels: Vec<WelcomeScreenChannelInfo>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WelcomeScreenChannelInfo {
     /// the channel's id
     pub channel_id: Snowflake,
@@ -230,7 +240,7 @@ pub struct WelcomeScreenChannelInfo {
 }
 
 /// https://discord.com/developers/docs/topics/gateway#client-status-object
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientStatusInfo {
     /// the user's status set for an active desktop (Windows, Linux, Mac) application session
     pub desktop: Option<String>,
@@ -240,14 +250,15 @@ pub struct ClientStatusInfo {
     pub web: Option<String>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// https://discord.com/developers/docs/resources/voice#voice-state-object
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VoiceState {
     /// the guild id this voice state is for
-    pub guild_id: Option<String>,
+    pub guild_id: Option<Snowflake>,
     /// the channel id this user is connected to
-    pub channel_id: Option<String>,
+    pub channel_id: Option<Snowflake>,
     /// the user id this voice state is for
-    pub user_id: String,
+    pub user_id: Snowflake,
     /// the guild member this voice state is for
     pub member: Option<GuildMemberInfo>,
     /// the session id for this voice state
@@ -268,7 +279,7 @@ pub struct VoiceState {
     pub suppress: bool
 }
 
-#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq)]
 #[repr(u8)]
 pub enum GuildVerificationLevel {
     /// unrestricted
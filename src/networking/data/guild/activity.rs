@@ -3,7 +3,7 @@ use serde_repr::*;
 
 use super::super::Snowflake;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityInfo {
     /// the activity's name
     pub name: String,
@@ -38,7 +38,7 @@ pub struct ActivityInfo {
 }
 
 /// https://discord.com/developers/docs/topics/gateway#activity-object-activity-timestamps
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityTimestamps {
     /// unix time (in milliseconds) of when the activity started
     pub start: Option<u64>,
@@ -47,7 +47,7 @@ pub struct ActivityTimestamps {
 }
 
 /// https://discord.com/developers/docs/topics/gateway#activity-object-activity-emoji
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityEmoji {
     /// the name of the emoji
     pub name: String,
@@ -58,7 +58,7 @@ pub struct ActivityEmoji {
 }
 
 /// https://discord.com/developers/docs/topics/gateway#activity-object-activity-party
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityParty {
     /// the id of the party
     pub id: Option<String>,
@@ -67,7 +67,7 @@ pub struct ActivityParty {
 }
 
 /// https://discord.com/developers/docs/topics/gateway#activity-object-activity-assets
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityAssets {
     /// the id for a large asset of the activity, usually a snowflake
     pub large_image: Option<String>,
@@ -79,7 +79,7 @@ pub struct ActivityAssets {
     pub small_text: Option<String>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivitySecrets {
     /// the secret for joining a party
     pub join: Option<String>,
@@ -91,7 +91,7 @@ pub struct ActivitySecrets {
 }
 
 /// https://discord.com/developers/docs/topics/gateway#activity-object-activity-types
-#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq)]
 #[repr(u8)]
 pub enum ActivityType {
     Game = 0,
@@ -3,12 +3,12 @@ use serde_repr::*;
 use serde::{Deserialize, Serialize};
 use self::embed::EmbedInfo;
 
-use super::{Snowflake, channel::ChannelTypes, guild::{GuildEmojiInfo, GuildMemberInfo}, user::UserInfo};
+use super::{Snowflake, Timestamp, channel::ChannelTypes, guild::{GuildEmojiInfo, GuildMemberInfo}, user::UserInfo};
 
 pub mod embed;
 
 /// https://discord.com/developers/docs/resources/channel#message-object
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageInfo {
     /// id of the message
     pub id: Snowflake,
@@ -23,9 +23,9 @@ pub struct MessageInfo {
     /// contents of the message
     pub content: String,
     /// when this message was sent
-    pub timestamp: String,
+    pub timestamp: Timestamp,
     /// when this message was edited (or null if never)
-    pub edited_timestamp: Option<String>,
+    pub edited_timestamp: Option<Timestamp>,
     /// whether this was a TTS message
     pub tts: bool,
     /// whether this message mentions everyone
@@ -68,8 +68,23 @@ pub struct MessageInfo {
     pub interaction: Option<MessageInteraction>
 }
 
+/// The changes to a message sent with edit message, fields that are `None` aren't changed.
+/// https://discord.com/developers/docs/resources/channel#edit-message-jsonform-params
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EditMessage {
+    /// the new contents of the message, up to 2000 characters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// the embeds that replace the current ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embeds: Option<Vec<EmbedInfo>>,
+    /// only `SUPRESS_EMBEDS` can be set or unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags: Option<MessageFlags>
+}
+
 /// https://discord.com/developers/docs/resources/channel#channel-mention-object
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelMentionInfo {
     /// id of the channel
     pub id: Snowflake,
@@ -83,7 +98,7 @@ pub struct ChannelMentionInfo {
 }
 
 /// https://discord.com/developers/docs/resources/channel#attachment-object
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageAttachmentInfo {
     /// attachment id
     pub id: Snowflake,
@@ -102,7 +117,7 @@ pub struct MessageAttachmentInfo {
 }
 
 /// https://discord.com/developers/docs/resources/channel#reaction-object
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReactionInfo {
     /// times this emoji has been used to react
     pub count: i32,
@@ -113,7 +128,7 @@ pub struct ReactionInfo {
 }
 
 /// https://discord.com/developers/docs/resources/channel#message-object-message-types
-#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq)]
 #[repr(u8)]
 pub enum MessageType {
     Default,
@@ -136,7 +151,7 @@ pub enum MessageType {
 }
 
 /// https://discord.com/developers/docs/resources/channel#message-object-message-activity-structure
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageActivity {
     /// type of message activity
     #[serde(rename="type")]
@@ -146,7 +161,7 @@ pub struct MessageActivity {
 }
 
 /// https://discord.com/developers/docs/resources/channel#message-object-message-activity-types
-#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq)]
 #[repr(u8)]
 pub enum MessageActivityType {
     Join = 1,
@@ -156,7 +171,7 @@ pub enum MessageActivityType {
 }
 
 /// https://discord.com/developers/docs/resources/channel#message-object-message-application-structure
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageApplication {
     /// id of the application
     pub id: Snowflake,
@@ -171,7 +186,7 @@ pub struct MessageApplication {
 }
 
 /// https://discord.com/developers/docs/resources/channel#message-object-message-reference-structure
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageReference {
     /// id of the originating message
     pub message_id: Option<Snowflake>,
@@ -202,7 +217,7 @@ bitflags! {
 }
 
 /// https://discord.com/developers/docs/resources/channel#message-object-message-sticker-structure
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageSticker {
     /// id of the sticker
     pub id: Snowflake,
@@ -223,7 +238,7 @@ pub struct MessageSticker {
 }
 
 /// https://discord.com/developers/docs/interactions/slash-commands#messageinteraction
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageInteraction {
     /// id of the interaction
     pub id: Snowflake,
@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+use super::super::Timestamp;
+
 /// https://discord.com/developers/docs/resources/channel#embed-object
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedInfo {
     /// title of embed
     pub title: Option<String>,
@@ -13,7 +15,7 @@ pub struct EmbedInfo {
     /// url of embed
     pub url: Option<String>,
     /// timestamp of embed content
-    pub timestamp: Option<String>,
+    pub timestamp: Option<Timestamp>,
     /// color code of the embed
     pub color: Option<i32>,
     /// footer information
@@ -33,7 +35,7 @@ pub struct EmbedInfo {
 }
 
 /// https://discord.com/developers/docs/resources/channel#embed-object-embed-footer-structure
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedFooterInfo {
     /// footer text
     pub text: String,
@@ -44,7 +46,7 @@ pub struct EmbedFooterInfo {
 }
 
 /// https://discord.com/developers/docs/resources/channel#embed-object-embed-image-structure
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedMediaInfo {
     /// source url of image (only supports http(s) and attachments)
     #[serde(alias="icon_url")]
@@ -59,7 +61,7 @@ pub struct EmbedMediaInfo {
 }
 
 /// https://discord.com/developers/docs/resources/channel#embed-object-embed-provider-structure
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedProviderInfo {
     /// name of provider
     pub name: Option<String>,
@@ -68,7 +70,7 @@ pub struct EmbedProviderInfo {
 }
 
 /// https://discord.com/developers/docs/resources/channel#embed-object-embed-field-structure
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedFieldInfo {
     /// name of the field
     pub name: String,
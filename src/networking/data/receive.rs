@@ -1,21 +1,25 @@
+use std::{sync::Arc, time::Duration};
+
+use rand::Rng;
 use tokio::sync::mpsc;
-use tokio_tungstenite::tungstenite::Message;
 
+use super::event::DispatchEvent;
 use super::gateway::{GatewayPayload, GatewayOpCodes, GatewayPayloadData};
 use super::sendable;
+use crate::cache::Cache;
+use crate::networking::{session::GatewaySession, shard::{ShardManager, ShardPayload}};
 use crate::ui;
 
 mod dispatch;
 
-pub async fn handle_messages(client: reqwest::Client, mut wss_receive_rx: mpsc::Receiver<GatewayPayload>, wss_write_tx: futures_channel::mpsc::UnboundedSender<Message>, ui: ui::RustcordUI) {
+/// Handles the payloads received by every shard, replies are sent through the shard that received the payload
+pub async fn handle_messages(mut wss_receive_rx: mpsc::Receiver<ShardPayload>, shards: Arc<ShardManager>, cache: Arc<Cache>, ui: ui::RustcordUI) {
     loop {
-        let client = client.clone();
-        let wss_write_tx = wss_write_tx.clone();
         let ui = ui.clone();
 
         // Wait until a message is received from the wss receiver
         // If None is returned, stop the message handling loop
-        let message = match wss_receive_rx.recv().await {
+        let ShardPayload { shard_id, connection, payload: message } = match wss_receive_rx.recv().await {
             Some(m) => m,
             None => {
                 info!("All receive senders have been dropped");
@@ -23,22 +27,50 @@ pub async fn handle_messages(client: reqwest::Client, mut wss_receive_rx: mpsc::
             }
         };
 
-        debug!("Received from websocket -> {:?}", message);
+        debug!("Received from websocket of shard {} -> {:?}", shard_id, message);
+
+        let (session, wss_write_tx) = match shards.shard(shard_id) {
+            Some(shard) => (shard.session.clone(), shard.write_tx.clone()),
+            None => {
+                warn!("Received a payload from unknown shard {}", shard_id);
+                continue;
+            }
+        };
 
         // Call different methods depending on the opcode of the payload
         match message.op {
             GatewayOpCodes::Hello => {
-                handle_hello(message, wss_write_tx).await;
+                handle_hello(message, wss_write_tx, session, connection).await;
             },
             GatewayOpCodes::Dispatch => {
-                handle_dispatch(message, wss_write_tx, client, ui).await;
-            }
+                handle_dispatch(message, wss_write_tx, &cache, ui).await;
+            },
+            GatewayOpCodes::Heartbeat => {
+                // The gateway can ask for a heartbeat to be sent immediately
+                sendable::send_heartbeat(&wss_write_tx, &session);
+            },
+            GatewayOpCodes::HeartbeatAck => {
+                handle_heartbeat_ack(session, ui);
+            },
+            GatewayOpCodes::Reconnect => {
+                handle_reconnect(session, connection);
+            },
+            GatewayOpCodes::InvalidSession => {
+                // Handled in another thread because it has to wait before identifying again
+                tokio::spawn(handle_invalid_session(message, wss_write_tx, session, connection));
+            },
             _ => {}
         }
     }
 }
 
-async fn handle_hello(message: GatewayPayload, wss_write_tx: futures_channel::mpsc::UnboundedSender<Message>) {
+async fn handle_hello(message: GatewayPayload, wss_write_tx: futures_channel::mpsc::UnboundedSender<GatewayPayload>, session: Arc<GatewaySession>, connection: u32) {
+    // The connection that said hello is already closed, the new one starts its heartbeat and session when it says hello
+    if session.connection() != connection {
+        debug!("Ignoring the hello of a previous connection");
+        return;
+    }
+
     // Get the data from the message
     // If there's no data, print a warn and return
     let data = match message.d {
@@ -52,7 +84,7 @@ async fn handle_hello(message: GatewayPayload, wss_write_tx: futures_channel::mp
     // Get the heartbeat interval if the payload data is a HelloData variant
     // If it's some other variant, print a warn and return
     let heartbeat_interval = match data {
-        GatewayPayloadData::HelloData { heartbeat_interval, .. } => heartbeat_interval,
+        GatewayPayloadData::HelloData { heartbeat_interval } => heartbeat_interval,
         _ => {
             warn!("Invalid data in websocket hello message");
             return;
@@ -60,24 +92,107 @@ async fn handle_hello(message: GatewayPayload, wss_write_tx: futures_channel::mp
     };
 
     // Start a thread for sending heartbeats
-    tokio::spawn(sendable::start_heartbeat(heartbeat_interval, wss_write_tx.clone()));
+    tokio::spawn(sendable::start_heartbeat(heartbeat_interval, wss_write_tx.clone(), session.clone(), connection));
+
+    // Resume the previous session if there is one, otherwise start a new one.
+    // Identifying waits for the other shards in the same bucket, so it's done in another thread to keep handling their payloads
+    if session.can_resume() {
+        sendable::send_resume(session, wss_write_tx, connection).await;
+    } else {
+        tokio::spawn(sendable::send_identify(session, wss_write_tx, connection));
+    }
 }
 
-async fn handle_dispatch(message: GatewayPayload, wss_write_tx: futures_channel::mpsc::UnboundedSender<Message>, client: reqwest::Client, ui: ui::RustcordUI) {
-    // Get the data from the message
-    // If there's no data, print a warn and return
-    let data = match message.d {
-        Some(d) => d,
-        None => {
-            warn!("No data in websocket dispatch message");
+fn handle_heartbeat_ack(session: Arc<GatewaySession>, mut ui: ui::RustcordUI) {
+    // The ACK was already recorded when it was read from the websocket
+    if let Some(latency) = session.latency() {
+        trace!("Heartbeat acknowledged after {:?}", latency);
+        ui.set_ping(latency);
+    }
+}
+
+fn handle_reconnect(session: Arc<GatewaySession>, connection: u32) {
+    // The session is resumed once the new connection says hello
+    info!("The gateway asked to reconnect");
+    session.request_reconnect(connection);
+}
+
+async fn handle_invalid_session(message: GatewayPayload, wss_write_tx: futures_channel::mpsc::UnboundedSender<GatewayPayload>, session: Arc<GatewaySession>, connection: u32) {
+    // The shard already reconnected, so it's about a session the new connection doesn't use
+    if session.connection() != connection {
+        debug!("Ignoring an invalid session of a previous connection");
+        return;
+    }
+
+    // Get whether the session can be resumed from the message
+    // If there's no data, assume it can't be
+    let resumable = match message.d {
+        Some(GatewayPayloadData::InvalidSessionData(resumable)) => resumable,
+        _ => {
+            warn!("Invalid data in websocket invalid session message");
+            false
+        }
+    };
+
+    info!("The gateway session has been invalidated, resumable: {}", resumable);
+    if !resumable {
+        session.invalidate();
+    }
+
+    // Wait a random amount of time between 1 and 5 seconds, as required by discord.
+    // If the shard reconnects in the meantime, the new connection resumes or identifies on its own
+    let delay = rand::thread_rng().gen_range(1000..=5000);
+    tokio::time::sleep(Duration::from_millis(delay)).await;
+
+    if session.can_resume() {
+        sendable::send_resume(session, wss_write_tx, connection).await;
+    } else {
+        sendable::send_identify(session, wss_write_tx, connection).await;
+    }
+}
+
+async fn handle_dispatch(message: GatewayPayload, wss_write_tx: futures_channel::mpsc::UnboundedSender<GatewayPayload>, cache: &Cache, ui: ui::RustcordUI) {
+    // Get the event from the message
+    // If there's no event, print a warn and return
+    let event = match message.d {
+        Some(GatewayPayloadData::Dispatch(event)) => *event,
+        _ => {
+            warn!("No event in websocket dispatch message");
             return;
         }
     };
 
-    // Match the variant of data (ignore the values) and call a function to handle the variant
-    match data {
-        GatewayPayloadData::PresenceUpdateData { .. } => dispatch::handle_presence_update(data, client).await,
-        GatewayPayloadData::MessageCreateData { .. } => dispatch::handle_message_create(data, ui).await,
+    // Update the cache first so the handlers and the UI see the changes
+    cache.update(&event);
+
+    // Call a function to handle the event
+    match event {
+        DispatchEvent::Ready(ready) => dispatch::handle_ready(*ready, ui).await,
+        DispatchEvent::PresenceUpdate(presence) => dispatch::handle_presence_update(presence, ui).await,
+        DispatchEvent::MessageCreate(message) => dispatch::handle_message_create(*message, ui).await,
+        DispatchEvent::MessageUpdate(update) => dispatch::handle_message_update(update, ui).await,
+        DispatchEvent::MessageDelete(delete) => dispatch::handle_message_delete(delete, ui).await,
+        DispatchEvent::MessageDeleteBulk(delete) => dispatch::handle_message_delete_bulk(delete, ui).await,
+        DispatchEvent::MessageReactionAdd(reaction) => dispatch::handle_message_reaction_add(reaction, ui).await,
+        DispatchEvent::MessageReactionRemove(reaction) => dispatch::handle_message_reaction_remove(reaction, ui).await,
+        DispatchEvent::MessageReactionRemoveAll(data) => dispatch::handle_message_reaction_remove_all(data, ui).await,
+        DispatchEvent::MessageReactionRemoveEmoji(data) => dispatch::handle_message_reaction_remove_emoji(data, ui).await,
+        DispatchEvent::ChannelPinsUpdate(data) => dispatch::handle_channel_pins_update(data, cache, ui).await,
+        DispatchEvent::ChannelCreate(channel) | DispatchEvent::ChannelUpdate(channel) => dispatch::handle_channel_update(*channel, ui).await,
+        DispatchEvent::ChannelDelete(channel) => dispatch::handle_channel_delete(*channel, ui).await,
+        DispatchEvent::ChannelRecipientAdd(data) => dispatch::handle_channel_recipient_add(data, ui).await,
+        DispatchEvent::ChannelRecipientRemove(data) => dispatch::handle_channel_recipient_remove(data, ui).await,
+        DispatchEvent::GuildCreate(guild) => dispatch::handle_guild_create(*guild, ui).await,
+        DispatchEvent::GuildUpdate(guild) => dispatch::handle_guild_update(*guild, ui).await,
+        DispatchEvent::GuildDelete(guild) => dispatch::handle_guild_delete(guild, cache, ui).await,
+        DispatchEvent::GuildMemberAdd(data) => dispatch::handle_guild_member_add(data, ui).await,
+        DispatchEvent::GuildMemberUpdate(data) => dispatch::handle_guild_member_update(data, ui).await,
+        DispatchEvent::GuildMemberRemove(data) => dispatch::handle_guild_member_remove(data, ui).await,
+        DispatchEvent::GuildMembersChunk(data) => dispatch::handle_guild_members_chunk(data, ui).await,
+        DispatchEvent::GuildRoleCreate(data) | DispatchEvent::GuildRoleUpdate(data) => dispatch::handle_guild_role_update(data, ui).await,
+        DispatchEvent::GuildRoleDelete(data) => dispatch::handle_guild_role_delete(data, ui).await,
+        DispatchEvent::GuildEmojisUpdate(data) => dispatch::handle_guild_emojis_update(data).await,
+        DispatchEvent::Unknown { name, raw } => trace!("Ignoring unknown event {}: {}", name, raw),
         _ => {}
     };
 }
\ No newline at end of file
@@ -1,41 +1,120 @@
-use fltk::BrowserExt;
-
-use crate::networking::data::gateway::GatewayPayloadData;
+use crate::cache::Cache;
+use crate::networking::data::{channel::ChannelInfo, timestamp, event::{ChannelPinsUpdateData, ChannelRecipientData, GuildEmojisUpdateData, GuildMemberAddData, GuildMemberRemoveData, GuildMemberUpdateData, GuildMembersChunkData, GuildRoleData, GuildRoleDeleteData, ReadyData, UnavailableGuildData, MessageDeleteBulkData, MessageDeleteData, MessageReactionData, MessageReactionRemoveAllData, MessageReactionRemoveEmojiData, MessageUpdateData, PresenceUpdateData}, guild::GuildInfo, message::MessageInfo};
 use crate::ui;
 
-pub async fn handle_presence_update(data: GatewayPayloadData, client: reqwest::Client) {
-    // Get the data from the data argument
-    // If it's not a PresenceUpdateData variant, return and print a warn
-    let (user, status, last_modified, client_status, activities) = match data {
-        GatewayPayloadData::PresenceUpdateData { user, status, last_modified, client_status, activities } 
-          => (user, status, last_modified, client_status, activities),
-        _ => {
-            warn!("Invalid data passed to handle_presence_update");
-            return;
-        }
-    };
-
-    // Send a message containing the activities of the user when their presence is updated
-    tokio::spawn(async move {
-        super::sendable::send_message(client.clone(), format!("Hi {:?}, your rpc is: `{:?}`", user.username, activities.first()), "829119138475671602".to_string()).await;
-    });
-}
-
-pub async fn handle_message_create(data: GatewayPayloadData, mut ui: ui::RustcordUI) {
-    // Get the data from the data argument
-    // If it's not a MessageCreateData, return and print a warn
-    let message_data = match data {
-        GatewayPayloadData::MessageCreateData { message_data }
-          => message_data,
-        _ => {
-            warn!("Invalid data passed to handle_message_create");
-            return;
+pub async fn handle_ready(ready: ReadyData, mut ui: ui::RustcordUI) {
+    // Every shard gets its own READY, so the guilds of the other shards are kept
+    ui.add_guilds(ready.available_guilds());
+    ui.add_private_channels(&ready.private_channels);
+}
+
+pub async fn handle_presence_update(presence: PresenceUpdateData, mut ui: ui::RustcordUI) {
+    ui.update_presence(&presence.user.id);
+}
+
+pub async fn handle_message_create(message_data: MessageInfo, mut ui: ui::RustcordUI) {
+    ui.add_message(&message_data);
+}
+
+pub async fn handle_message_update(update: MessageUpdateData, mut ui: ui::RustcordUI) {
+    ui.update_message(&update);
+}
+
+pub async fn handle_message_delete(delete: MessageDeleteData, mut ui: ui::RustcordUI) {
+    ui.delete_messages(&[delete.id]);
+}
+
+pub async fn handle_message_delete_bulk(delete: MessageDeleteBulkData, mut ui: ui::RustcordUI) {
+    ui.delete_messages(&delete.ids);
+}
+
+pub async fn handle_message_reaction_add(reaction: MessageReactionData, mut ui: ui::RustcordUI) {
+    ui.add_reaction(&reaction);
+}
+
+pub async fn handle_message_reaction_remove(reaction: MessageReactionData, mut ui: ui::RustcordUI) {
+    ui.remove_reaction(&reaction);
+}
+
+pub async fn handle_message_reaction_remove_all(data: MessageReactionRemoveAllData, mut ui: ui::RustcordUI) {
+    ui.remove_reactions(&data.message_id, None);
+}
+
+pub async fn handle_message_reaction_remove_emoji(data: MessageReactionRemoveEmojiData, mut ui: ui::RustcordUI) {
+    ui.remove_reactions(&data.message_id, Some(&data.emoji));
+}
+
+pub async fn handle_channel_pins_update(data: ChannelPinsUpdateData, cache: &Cache, mut ui: ui::RustcordUI) {
+    // The event doesn't say which message was pinned or unpinned, the messages themselves get a MESSAGE_UPDATE
+    let channel = cache.channel(&data.channel_id).and_then(|channel| channel.name).unwrap_or_else(|| "this channel".to_string());
+    match data.last_pin_timestamp {
+        Some(time) => ui.show_status(&format!("Pinned messages in {} updated, last pin {}", channel, timestamp::format_relative(&time, cache.timezone_offset()))),
+        None => ui.show_status(&format!("Pinned messages in {} updated, there are no pins left", channel))
+    }
+}
+
+pub async fn handle_guild_create(guild: GuildInfo, mut ui: ui::RustcordUI) {
+    ui.add_guild(&guild);
+}
+
+pub async fn handle_guild_update(guild: GuildInfo, mut ui: ui::RustcordUI) {
+    ui.update_guild(&guild);
+}
+
+pub async fn handle_guild_delete(guild: UnavailableGuildData, cache: &Cache, mut ui: ui::RustcordUI) {
+    // Guilds that are unavailable because of an outage are sent again in a GUILD_CREATE once they're back
+    let unavailable = guild.unavailable.unwrap_or(false);
+    if unavailable {
+        match cache.guild(&guild.id) {
+            Some(cached) => warn!("Guild {} ({:?}) is unavailable", cached.name, guild.id),
+            None => warn!("Guild {:?} is unavailable", guild.id)
         }
-    };
-
-    // Do some pretty formatting to the message and then add it to the ui
-    let username = message_data.author.username.unwrap_or("missing_username".to_string());
-    let discriminator = message_data.author.discriminator.unwrap_or("missing_discriminator".to_string());
-    let content = format!("<{}#{}>: {}", username, discriminator, message_data.content);
-    ui.chat_messages.add(content.as_str());
-}
\ No newline at end of file
+    }
+    ui.remove_guild(&guild.id, unavailable);
+}
+
+pub async fn handle_guild_member_add(data: GuildMemberAddData, mut ui: ui::RustcordUI) {
+    ui.add_members(&data.guild_id, &[data.member]);
+}
+
+pub async fn handle_guild_member_update(data: GuildMemberUpdateData, mut ui: ui::RustcordUI) {
+    ui.update_member(&data.guild_id, &data.user, data.nick.as_ref(), &data.roles);
+}
+
+pub async fn handle_guild_member_remove(data: GuildMemberRemoveData, mut ui: ui::RustcordUI) {
+    ui.remove_member(&data.guild_id, &data.user.id);
+}
+
+pub async fn handle_guild_members_chunk(data: GuildMembersChunkData, mut ui: ui::RustcordUI) {
+    trace!("Received member chunk {} of {} for guild {:?}", data.chunk_index + 1, data.chunk_count, data.guild_id);
+    ui.add_members(&data.guild_id, &data.members);
+}
+
+pub async fn handle_guild_role_update(data: GuildRoleData, mut ui: ui::RustcordUI) {
+    ui.set_role(&data.guild_id, &data.role);
+}
+
+pub async fn handle_guild_role_delete(data: GuildRoleDeleteData, mut ui: ui::RustcordUI) {
+    ui.remove_role(&data.guild_id, &data.role_id);
+}
+
+pub async fn handle_guild_emojis_update(data: GuildEmojisUpdateData) {
+    // Emojis aren't shown in the sidebar or the member list
+    debug!("Guild {:?} now has {} emojis", data.guild_id, data.emojis.len());
+}
+
+pub async fn handle_channel_update(channel: ChannelInfo, mut ui: ui::RustcordUI) {
+    ui.set_channel(&channel);
+}
+
+pub async fn handle_channel_delete(channel: ChannelInfo, mut ui: ui::RustcordUI) {
+    ui.remove_channel(&channel);
+}
+
+pub async fn handle_channel_recipient_add(data: ChannelRecipientData, mut ui: ui::RustcordUI) {
+    ui.add_recipient(&data.channel_id, &data.user);
+}
+
+pub async fn handle_channel_recipient_remove(data: ChannelRecipientData, mut ui: ui::RustcordUI) {
+    ui.remove_recipient(&data.channel_id, &data.user.id);
+}
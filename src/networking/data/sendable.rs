@@ -1,10 +1,13 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use tokio_tungstenite::tungstenite::Message;
+use rand::Rng;
+use crate::networking::session::GatewaySession;
+use super::{gateway::{BotIdentifyProperties, GatewayIntents, GatewayOpCodes, GatewayPayload, GatewayPayloadData, IdentifyProperties}};
 
-use super::{gateway::{GatewayOpCodes, GatewayPayload, GatewayPayloadData, IdentifyProperties}};
+/// Guilds with more members than this won't have their offline members sent
+const LARGE_THRESHOLD: u8 = 250;
 
-pub fn create_identify_message(token: &String) -> GatewayPayload {
+pub fn create_identify_message(token: &str) -> GatewayPayload {
     GatewayPayload {
         op: GatewayOpCodes::Identify,
         s: None,
@@ -21,57 +24,120 @@ pub fn create_identify_message(token: &String) -> GatewayPayload {
     }
 }
 
-pub fn create_heartbeat_message() -> GatewayPayload {
+pub fn create_bot_identify_message(token: &str, intents: GatewayIntents, shard: [u32; 2]) -> GatewayPayload {
+    GatewayPayload {
+        op: GatewayOpCodes::Identify,
+        s: None,
+        t: None,
+        d: Some(GatewayPayloadData::BotIdentifyData {
+            token: token.to_owned(),
+            intents,
+            shard,
+            large_threshold: LARGE_THRESHOLD,
+            properties: BotIdentifyProperties {
+                os: std::env::consts::OS.to_string(),
+                browser: "rustcord".to_string(),
+                device: "rustcord".to_string()
+            }
+        })
+    }
+}
+
+pub fn create_resume_message(token: &str, session_id: String, seq: i32) -> GatewayPayload {
+    GatewayPayload {
+        op: GatewayOpCodes::Resume,
+        s: None,
+        t: None,
+        d: Some(GatewayPayloadData::ResumeData {
+            token: token.to_owned(),
+            session_id,
+            seq
+        })
+    }
+}
+
+pub fn create_heartbeat_message(sequence: Option<i32>) -> GatewayPayload {
     GatewayPayload {
         op: GatewayOpCodes::Heartbeat,
         s: None,
         t: None,
-        d: None
+        d: sequence.map(GatewayPayloadData::HeartbeatData)
     }
 }
 
-pub async fn start_heartbeat(delay: u32, write_tx: futures_channel::mpsc::UnboundedSender<Message>) {
+/// Sends a single heartbeat containing the last sequence number received by the session
+pub fn send_heartbeat(write_tx: &futures_channel::mpsc::UnboundedSender<GatewayPayload>, session: &GatewaySession) {
+    trace!("Sending heartbeat");
+    let send_result = write_tx.unbounded_send(create_heartbeat_message(session.sequence()));
+    if let Err(e) = send_result {
+        error!("Failed to send the heartbeat with error: {}", e);
+    }
+}
+
+/// Sends a heartbeat every `delay` milliseconds until the websocket connection `connection` is closed.
+/// If a heartbeat isn't acknowledged before the next one is due, the connection is considered dead and a reconnect is requested
+pub async fn start_heartbeat(delay: u32, write_tx: futures_channel::mpsc::UnboundedSender<GatewayPayload>, session: Arc<GatewaySession>, connection: u32) {
     trace!("Started heartbeat thread");
-    let mut interval = tokio::time::interval(Duration::from_millis(delay as u64));
+    let delay = Duration::from_millis(delay as u64);
+
+    // The first heartbeat is sent after a random fraction of the interval, so clients don't all send them at the same time
+    let jitter = delay.mul_f64(rand::thread_rng().gen::<f64>());
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + jitter, delay);
     loop {
         interval.tick().await;
-        trace!("Sending heartbeat");
-        let heartbeat_json = match serde_json::to_string(&create_heartbeat_message()) {
-            Ok(j) => j,
-            Err(e) => {
-                error!("Failed to create the heartbeat json with error: {}", e);
-                return;
-            }
-        };
+        if session.connection() != connection {
+            trace!("Stopping the heartbeat of a closed connection");
+            return;
+        }
 
-        let send_result = write_tx.unbounded_send(Message::text(heartbeat_json));
-        if let Err(e) = send_result {
-            error!("Failed to send the heartbeat with error: {}", e);
+        if !session.heartbeat_sent() {
+            warn!("The last heartbeat wasn't acknowledged, reconnecting");
+            session.request_reconnect(connection);
+            return;
         }
+
+        send_heartbeat(&write_tx, &session);
     }
 }
 
-pub async fn send_message(client: reqwest::Client, content: String, channel_id: String) {
-    let url = format!("https://discord.com/api/v8/channels/{}/messages", channel_id);
-    let json = serde_json::json!({
-        "content": &content
-    });
-
-    // create the post request and send it
-    let res = client  
-        .post(&url)
-        .json(&json)
-        .send()
-        .await.unwrap();
+/// Identifies as a bot if the session has a bot token, otherwise as a user.
+/// Nothing is sent if the shard reconnected since `connection` while waiting, the new connection identifies on its own
+pub async fn send_identify(session: Arc<GatewaySession>, tx: futures_channel::mpsc::UnboundedSender<GatewayPayload>, connection: u32) {
+    session.wait_for_identify().await;
+    if session.connection() != connection {
+        debug!("Not identifying on a closed connection");
+        return;
+    }
 
-    trace!("Sent message to {} with response: {:?}", url, res);
-    trace!("Content is: {}", json);
+    let payload = if session.is_bot() {
+        create_bot_identify_message(session.token(), session.intents(), session.shard())
+    } else {
+        create_identify_message(session.token())
+    };
+    if let Err(e) = tx.unbounded_send(payload) {
+        error!("Failed to send the identify message with error: {}", e);
+        return;
+    }
+    trace!("Sent identify message");
 }
 
-pub async fn send_identify(token: String, tx: futures_channel::mpsc::UnboundedSender<Message>) {
-    tokio::time::sleep(Duration::new(1, 0)).await;
-    let payload_data = create_identify_message(&token);
-    let data = serde_json::to_string(&payload_data).unwrap();
-    tx.unbounded_send(Message::text(data)).unwrap();
-    trace!("Sent identify message");
+/// Nothing is sent if the shard reconnected since `connection`, the new connection resumes on its own
+pub async fn send_resume(session: Arc<GatewaySession>, tx: futures_channel::mpsc::UnboundedSender<GatewayPayload>, connection: u32) {
+    if session.connection() != connection {
+        debug!("Not resuming on a closed connection");
+        return;
+    }
+    let session_id = match session.session_id() {
+        Some(id) => id,
+        None => {
+            warn!("Tried to resume without a session id");
+            return;
+        }
+    };
+
+    if let Err(e) = tx.unbounded_send(create_resume_message(session.token(), session_id, session.sequence().unwrap_or(0))) {
+        error!("Failed to send the resume message with error: {}", e);
+        return;
+    }
+    trace!("Sent resume message");
 }
\ No newline at end of file
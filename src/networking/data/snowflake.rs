@@ -0,0 +1,122 @@
+use std::{convert::TryFrom, fmt, num::ParseIntError, str::FromStr, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::{self, Visitor}};
+
+/// The first millisecond of 2015, which the timestamps in snowflakes start counting from
+pub const DISCORD_EPOCH: u64 = 1_420_070_400_000;
+
+/// A unique id used by discord for users, guilds, channels, messages and everything else.
+/// It's sent as a string in json so it doesn't lose precision in javascript, but erlang term format sends it as an integer.
+/// https://discord.com/developers/docs/reference#snowflakes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Snowflake(u64);
+
+impl Snowflake {
+    pub const fn new(id: u64) -> Self {
+        Snowflake(id)
+    }
+
+    /// Milliseconds since the unix epoch when the snowflake was created
+    pub const fn timestamp(self) -> u64 {
+        (self.0 >> 22) + DISCORD_EPOCH
+    }
+
+    pub fn created_at(self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.timestamp())
+    }
+}
+
+impl From<u64> for Snowflake {
+    fn from(id: u64) -> Self {
+        Snowflake(id)
+    }
+}
+
+impl FromStr for Snowflake {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Snowflake)
+    }
+}
+
+impl fmt::Display for Snowflake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for Snowflake {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Snowflake {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(SnowflakeVisitor)
+    }
+}
+
+/// Accepts snowflakes as strings and as integers
+struct SnowflakeVisitor;
+
+impl<'de> Visitor<'de> for SnowflakeVisitor {
+    type Value = Snowflake;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a snowflake as a string or an integer")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Snowflake(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        u64::try_from(v).map(Snowflake).map_err(|_| E::invalid_value(de::Unexpected::Signed(v), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse().map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        std::str::from_utf8(v)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Bytes(v), &self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::Snowflake;
+
+    /// The example from https://discord.com/developers/docs/reference#snowflakes
+    const EXAMPLE: Snowflake = Snowflake::new(175928847299117063);
+
+    #[test]
+    fn deserializes_strings() {
+        assert_eq!(serde_json::from_str::<Snowflake>("\"175928847299117063\"").unwrap(), EXAMPLE);
+        assert!(serde_json::from_str::<Snowflake>("\"not a snowflake\"").is_err());
+    }
+
+    #[test]
+    fn deserializes_integers() {
+        assert_eq!(serde_json::from_str::<Snowflake>("175928847299117063").unwrap(), EXAMPLE);
+        assert!(serde_json::from_str::<Snowflake>("-1").is_err());
+    }
+
+    #[test]
+    fn serializes_as_string() {
+        assert_eq!(serde_json::to_string(&EXAMPLE).unwrap(), "\"175928847299117063\"");
+    }
+
+    #[test]
+    fn timestamp() {
+        assert_eq!(EXAMPLE.timestamp(), 1462015105796);
+        assert_eq!(EXAMPLE.created_at(), UNIX_EPOCH + Duration::from_millis(1462015105796));
+    }
+}
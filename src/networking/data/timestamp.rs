@@ -0,0 +1,88 @@
+use chrono::{DateTime, FixedOffset, Utc};
+
+/// An ISO-8601 timestamp sent by discord, like `2021-04-09T19:34:42.018000+00:00`
+pub type Timestamp = DateTime<Utc>;
+
+/// How long ago something happened, like "5 minutes ago".
+/// Anything older than a week is shown as a date in the timezone of the user instead
+pub fn format_relative(time: &Timestamp, timezone_offset: i32) -> String {
+    format_relative_to(time, &Utc::now(), timezone_offset)
+}
+
+fn format_relative_to(time: &Timestamp, now: &Timestamp, timezone_offset: i32) -> String {
+    let elapsed = now.signed_duration_since(*time);
+    let (amount, unit) = if elapsed.num_seconds() < 60 {
+        return "just now".to_string();
+    } else if elapsed.num_minutes() < 60 {
+        (elapsed.num_minutes(), "minute")
+    } else if elapsed.num_hours() < 24 {
+        (elapsed.num_hours(), "hour")
+    } else if elapsed.num_days() < 7 {
+        (elapsed.num_days(), "day")
+    } else {
+        return format_local(time, timezone_offset, "%Y-%m-%d");
+    };
+
+    if amount == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", amount, unit)
+    }
+}
+
+/// Formats a timestamp in the timezone of the user with a strftime format string.
+/// `timezone_offset` is `UserSettings.timezone_offset`, the minutes the user is behind UTC like in javascript's `getTimezoneOffset`
+pub fn format_local(time: &Timestamp, timezone_offset: i32, format: &str) -> String {
+    // Offsets of a day or more are invalid, use UTC for them
+    let offset = FixedOffset::west_opt(timezone_offset * 60).unwrap_or_else(|| FixedOffset::east(0));
+    time.with_timezone(&offset).format(format).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    fn time() -> Timestamp {
+        Utc.ymd(2021, 4, 9).and_hms(19, 34, 42)
+    }
+
+    fn ago(elapsed: Duration) -> String {
+        format_relative_to(&(time() - elapsed), &time(), 0)
+    }
+
+    #[test]
+    fn relative_units() {
+        assert_eq!(ago(Duration::seconds(59)), "just now");
+        assert_eq!(ago(Duration::seconds(60)), "1 minute ago");
+        assert_eq!(ago(Duration::seconds(119)), "1 minute ago");
+        assert_eq!(ago(Duration::minutes(2)), "2 minutes ago");
+        assert_eq!(ago(Duration::minutes(59)), "59 minutes ago");
+        assert_eq!(ago(Duration::hours(1)), "1 hour ago");
+        assert_eq!(ago(Duration::hours(2)), "2 hours ago");
+        assert_eq!(ago(Duration::days(1)), "1 day ago");
+        assert_eq!(ago(Duration::days(6)), "6 days ago");
+    }
+
+    #[test]
+    fn relative_dates_after_a_week() {
+        assert_eq!(ago(Duration::days(7)), "2021-04-02");
+        // The date is in the timezone of the user, 2021-04-02 19:34:42 UTC is already the next day in UTC+5
+        assert_eq!(format_relative_to(&(time() - Duration::days(7)), &time(), -300), "2021-04-03");
+    }
+
+    #[test]
+    fn local_time() {
+        // The offset is how far the user is behind UTC, so it's negative east of UTC
+        assert_eq!(format_local(&time(), -120, "%H:%M"), "21:34");
+        assert_eq!(format_local(&time(), 300, "%H:%M"), "14:34");
+        assert_eq!(format_local(&time(), 0, "%Y-%m-%d %H:%M"), "2021-04-09 19:34");
+    }
+
+    #[test]
+    fn invalid_offsets_use_utc() {
+        assert_eq!(format_local(&time(), 24 * 60, "%H:%M"), "19:34");
+        assert_eq!(format_local(&time(), -24 * 60, "%H:%M"), "19:34");
+    }
+}
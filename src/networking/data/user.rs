@@ -1,9 +1,9 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use super::{MuteConfig, Snowflake, guild::{GuildFolderInfo, GuildSettingChannelOverride}};
+use super::{MuteConfig, Snowflake, Timestamp, guild::{GuildFolderInfo, GuildSettingChannelOverride}};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserInfo {
     /// the user's id
     pub id: Snowflake,
@@ -39,9 +39,10 @@ pub struct UserInfo {
     desktop: Option<bool>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSettings {
-    timezone_offset: i32,
+    /// the minutes the user is behind UTC, like javascript's `getTimezoneOffset`
+    pub timezone_offset: i32,
     theme: String,
     stream_notifications_enabled: bool,
     pub status: String,
@@ -54,7 +55,7 @@ pub struct UserSettings {
     locale: String,
     inline_embed_media: bool,
     inline_attachment_media: bool,
-    guild_positions: Vec<String>, // How the channels are sorted in the left bar
+    guild_positions: Vec<Snowflake>, // How the channels are sorted in the left bar
     guild_folders: Vec<GuildFolderInfo>,
     gif_auto_play: bool,
     friend_source_flags: Value, // TODO: { "all": true }
@@ -74,7 +75,7 @@ pub struct UserSettings {
     afk_timeout: i32
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserGuildSettingEntry {
     version: i32,
     pub suppress_roles: bool,
@@ -84,25 +85,25 @@ pub struct UserGuildSettingEntry {
     mobile_push: bool,
     pub message_notifications: i32,
     pub hide_muted_channels: bool,
-    pub guild_id: Option<String>, // TODO
+    pub guild_id: Option<Snowflake>,
     pub channel_overrides: Vec<GuildSettingChannelOverride>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserRelationship {
-    pub user_id: String,
+    pub user_id: Snowflake,
     #[serde(rename="type")]
     pub relationship_type: u8,
-    nickname: Option<String>,
-    id: String
+    pub nickname: Option<String>,
+    pub id: Snowflake
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserCustomStatusInfo {
     pub text: String,
-    pub expires_at: String,
+    pub expires_at: Option<Timestamp>,
     pub emoji_name: String,
-    pub emoji_id: String
+    pub emoji_id: Option<Snowflake>
 }
 
 bitflags! {
@@ -0,0 +1,63 @@
+use std::{env, fs, path::PathBuf};
+
+use crate::config::Config;
+use super::{data::gateway::GatewayInfo, http::HttpClient};
+
+/// The gateway used when the REST api can't be reached and there's no cached url
+const DEFAULT_GATEWAY_URL: &str = "wss://gateway.discord.gg";
+
+/// Where the last gateway info received from the REST api is stored
+fn cache_path() -> Option<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+    Some(cache_dir.join("rustcord").join("gateway.json"))
+}
+
+fn load_cache() -> Option<GatewayInfo> {
+    let text = fs::read_to_string(cache_path()?).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn save_cache(info: &GatewayInfo) {
+    let path = match cache_path() {
+        Some(p) => p,
+        None => return
+    };
+
+    // The session start limit is only valid right now, so only the url is cached
+    let cached = GatewayInfo { url: info.url.clone(), shards: None, session_start_limit: None };
+    let result = path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, serde_json::to_string(&cached).unwrap()));
+    if let Err(e) = result {
+        warn!("Failed to cache the gateway url in {}: {}", path.display(), e);
+    }
+}
+
+/// Gets the gateway info from `GET /gateway`, or `GET /gateway/bot` for bots.
+/// The gateway url in the config is always used if it's set, bots still ask for their shards and session start limit.
+/// If the request fails, the url from the last successful request is used, or the default one if there's none
+pub async fn get_gateway_info(http: &HttpClient, config: &Config, bot: bool) -> GatewayInfo {
+    if let (Some(url), false) = (&config.gateway_url, bot) {
+        return GatewayInfo { url: url.clone(), shards: None, session_start_limit: None };
+    }
+
+    let path = if bot { "/gateway/bot" } else { "/gateway" };
+    let mut info = match http.get(path).await {
+        Ok(info) => {
+            debug!("Received gateway info: {:?}", info);
+            save_cache(&info);
+            info
+        },
+        Err(e) => {
+            warn!("Failed to get the gateway url from {}: {}", path, e);
+            load_cache().unwrap_or_else(|| GatewayInfo { url: DEFAULT_GATEWAY_URL.to_string(), shards: None, session_start_limit: None })
+        }
+    };
+    if let Some(url) = &config.gateway_url {
+        info.url = url.clone();
+    }
+    info
+}
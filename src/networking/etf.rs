@@ -0,0 +1,175 @@
+//! A serde implementation of the [Erlang External Term Format](https://erlang.org/doc/apps/erts/erl_ext_dist.html),
+//! as used by the gateway when connecting with `encoding=etf`.
+//! Integers sent where a string is expected are converted into strings, since the gateway sends snowflakes as integers
+
+use std::fmt;
+
+pub use self::de::from_slice;
+pub use self::ser::to_vec;
+
+mod de;
+mod ser;
+
+/// The version byte every term starts with
+const VERSION: u8 = 131;
+
+const NEW_FLOAT_EXT: u8 = 70;
+const COMPRESSED: u8 = 80;
+const SMALL_INTEGER_EXT: u8 = 97;
+const INTEGER_EXT: u8 = 98;
+const FLOAT_EXT: u8 = 99;
+const ATOM_EXT: u8 = 100;
+const SMALL_TUPLE_EXT: u8 = 104;
+const LARGE_TUPLE_EXT: u8 = 105;
+const NIL_EXT: u8 = 106;
+const STRING_EXT: u8 = 107;
+const LIST_EXT: u8 = 108;
+const BINARY_EXT: u8 = 109;
+const SMALL_BIG_EXT: u8 = 110;
+const LARGE_BIG_EXT: u8 = 111;
+const SMALL_ATOM_EXT: u8 = 115;
+const MAP_EXT: u8 = 116;
+const ATOM_UTF8_EXT: u8 = 118;
+const SMALL_ATOM_UTF8_EXT: u8 = 119;
+
+#[derive(Debug)]
+pub enum Error {
+    /// an error reported by the type being (de)serialized
+    Message(String),
+    /// the term didn't start with the version byte
+    InvalidVersion(u8),
+    /// a tag that isn't supported was found
+    UnsupportedTag(u8),
+    /// the input ended in the middle of a term
+    Eof,
+    /// there's data left after the term
+    TrailingData,
+    /// a compressed term couldn't be decompressed
+    Decompress(String),
+    /// an integer doesn't fit in 64 bits
+    IntegerTooLarge,
+    /// an atom or a string isn't valid
+    InvalidString
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Message(message) => write!(f, "{}", message),
+            Error::InvalidVersion(version) => write!(f, "invalid term version {}", version),
+            Error::UnsupportedTag(tag) => write!(f, "unsupported term tag {}", tag),
+            Error::Eof => write!(f, "unexpected end of input"),
+            Error::TrailingData => write!(f, "trailing data after the term"),
+            Error::Decompress(e) => write!(f, "failed to decompress the term: {}", e),
+            Error::IntegerTooLarge => write!(f, "integer doesn't fit in 64 bits"),
+            Error::InvalidString => write!(f, "invalid string")
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl serde::de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fmt::Debug};
+
+    use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+    use super::*;
+
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: T) {
+        let term = to_vec(&value).unwrap();
+        assert_eq!(from_slice::<T>(&term).unwrap(), value);
+    }
+
+    #[test]
+    fn atoms() {
+        assert_eq!(to_vec(&true).unwrap(), [VERSION, SMALL_ATOM_UTF8_EXT, 4, b't', b'r', b'u', b'e']);
+        round_trip(true);
+        round_trip(false);
+        assert!(!from_slice::<bool>(&[VERSION, ATOM_EXT, 0, 5, b'f', b'a', b'l', b's', b'e']).unwrap());
+        assert_eq!(from_slice::<String>(&[VERSION, SMALL_ATOM_EXT, 2, b'o', b'k']).unwrap(), "ok");
+    }
+
+    #[test]
+    fn nil_is_none() {
+        assert_eq!(to_vec(&None::<u32>).unwrap(), [VERSION, SMALL_ATOM_UTF8_EXT, 3, b'n', b'i', b'l']);
+        round_trip(None::<u32>);
+        round_trip(Some(5u32));
+        assert_eq!(from_slice::<Option<String>>(&[VERSION, ATOM_EXT, 0, 3, b'n', b'i', b'l']).unwrap(), None);
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(to_vec(&5u8).unwrap(), [VERSION, SMALL_INTEGER_EXT, 5]);
+        assert_eq!(to_vec(&-1i32).unwrap(), [VERSION, INTEGER_EXT, 255, 255, 255, 255]);
+        assert_eq!(to_vec(&(1u64 << 40)).unwrap(), [VERSION, SMALL_BIG_EXT, 6, 0, 0, 0, 0, 0, 0, 1]);
+        round_trip(0u8);
+        round_trip(1000i32);
+        round_trip(i32::MIN);
+        round_trip(175928847299117063u64);
+        round_trip(u64::MAX);
+        round_trip(i64::MIN);
+        assert_eq!(from_slice::<u64>(&[VERSION, LARGE_BIG_EXT, 0, 0, 0, 1, 0, 7]).unwrap(), 7);
+        assert!(matches!(from_slice::<u64>(&[VERSION, SMALL_BIG_EXT, 9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]), Err(Error::IntegerTooLarge)));
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(to_vec("hi").unwrap(), [VERSION, BINARY_EXT, 0, 0, 0, 2, b'h', b'i']);
+        round_trip("héllo".to_string());
+        round_trip(String::new());
+        // Snowflakes are sent as integers
+        assert_eq!(from_slice::<String>(&to_vec(&175928847299117063u64).unwrap()).unwrap(), "175928847299117063");
+        // Lists of small integers are sent as strings
+        assert_eq!(from_slice::<Vec<u8>>(&[VERSION, STRING_EXT, 0, 2, 1, 2]).unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(to_vec(&Vec::<u8>::new()).unwrap(), [VERSION, NIL_EXT]);
+        assert_eq!(to_vec(&[1u8]).unwrap(), [VERSION, LIST_EXT, 0, 0, 0, 1, SMALL_INTEGER_EXT, 1, NIL_EXT]);
+        round_trip(Vec::<u32>::new());
+        round_trip(vec![1u32, 70000, 3]);
+        round_trip(vec![vec!["a".to_string()], vec![]]);
+    }
+
+    #[test]
+    fn maps() {
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), vec![1u8]);
+        map.insert("b".to_string(), vec![]);
+        round_trip(map);
+        round_trip(BTreeMap::<String, u8>::new());
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Payload {
+            op: u8,
+            d: Option<Vec<String>>,
+            s: Option<i32>
+        }
+        round_trip(Payload { op: 10, d: Some(vec!["x".to_string()]), s: None });
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(from_slice::<u8>(&[130, SMALL_INTEGER_EXT, 1]), Err(Error::InvalidVersion(130))));
+        assert!(matches!(from_slice::<u8>(&[VERSION, SMALL_INTEGER_EXT]), Err(Error::Eof)));
+        assert!(matches!(from_slice::<u8>(&[VERSION, SMALL_INTEGER_EXT, 1, 2]), Err(Error::TrailingData)));
+        // Lengths that don't fit in the input fail without allocating them
+        assert!(matches!(from_slice::<Vec<u8>>(&[VERSION, LIST_EXT, 255, 255, 255, 255, SMALL_INTEGER_EXT, 1]), Err(Error::Eof)));
+        assert!(from_slice::<u8>(&[VERSION, COMPRESSED, 255, 255, 255, 255]).is_err());
+    }
+}
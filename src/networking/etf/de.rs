@@ -0,0 +1,342 @@
+use std::{convert::TryInto, io::Read, str};
+
+use flate2::read::ZlibDecoder;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor, value::SeqDeserializer};
+
+use super::*;
+
+/// Deserializes a term, starting with the version byte.
+/// Compressed terms are decompressed before being deserialized
+pub fn from_slice<T: DeserializeOwned>(input: &[u8]) -> Result<T, Error> {
+    match input {
+        [VERSION, COMPRESSED, rest @ ..] => {
+            if rest.len() < 4 {
+                return Err(Error::Eof);
+            }
+
+            // The size comes from the payload, so it only limits how much is decompressed instead of being allocated up front
+            let (size, data) = rest.split_at(4);
+            let size = u32::from_be_bytes(size.try_into().unwrap());
+            let mut term = Vec::new();
+            ZlibDecoder::new(data).take(size as u64).read_to_end(&mut term).map_err(|e| Error::Decompress(e.to_string()))?;
+            parse(&term)
+        },
+        [VERSION, rest @ ..] => parse(rest),
+        [version, ..] => Err(Error::InvalidVersion(*version)),
+        [] => Err(Error::Eof)
+    }
+}
+
+fn parse<'de, T: de::Deserialize<'de>>(input: &'de [u8]) -> Result<T, Error> {
+    let mut deserializer = Deserializer { input };
+    let value = T::deserialize(&mut deserializer)?;
+    if !deserializer.input.is_empty() {
+        return Err(Error::TrailingData);
+    }
+
+    Ok(value)
+}
+
+pub struct Deserializer<'de> {
+    input: &'de [u8]
+}
+
+/// An integer that was read from the input
+enum Integer {
+    Unsigned(u64),
+    Signed(i64)
+}
+
+impl<'de> Deserializer<'de> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'de [u8], Error> {
+        if self.input.len() < len {
+            return Err(Error::Eof);
+        }
+
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn peek_tag(&self) -> Result<u8, Error> {
+        self.input.first().copied().ok_or(Error::Eof)
+    }
+
+    /// Reads the name of an atom whose tag was already read
+    fn read_atom(&mut self, tag: u8) -> Result<&'de str, Error> {
+        let len = match tag {
+            ATOM_EXT | ATOM_UTF8_EXT => self.read_u16()? as usize,
+            _ => self.read_u8()? as usize
+        };
+
+        str::from_utf8(self.read_bytes(len)?).map_err(|_| Error::InvalidString)
+    }
+
+    /// Reads a big integer whose tag and length were already read
+    fn read_big(&mut self, len: usize) -> Result<Integer, Error> {
+        let sign = self.read_u8()?;
+        let digits = self.read_bytes(len)?;
+
+        // The digits are little endian, so anything past the 8th byte has to be zero
+        if digits.iter().skip(8).any(|digit| *digit != 0) {
+            return Err(Error::IntegerTooLarge);
+        }
+        let value = digits.iter().take(8).rev().fold(0u64, |value, digit| (value << 8) | *digit as u64);
+
+        if sign == 0 {
+            Ok(Integer::Unsigned(value))
+        } else if value <= i64::MAX as u64 + 1 {
+            Ok(Integer::Signed((value as i64).wrapping_neg()))
+        } else {
+            Err(Error::IntegerTooLarge)
+        }
+    }
+
+    /// Reads the next term if it's an integer
+    fn read_integer(&mut self) -> Result<Option<Integer>, Error> {
+        let integer = match self.peek_tag()? {
+            SMALL_INTEGER_EXT => {
+                self.read_u8()?;
+                Integer::Unsigned(self.read_u8()? as u64)
+            },
+            INTEGER_EXT => {
+                self.read_u8()?;
+                Integer::Signed(self.read_u32()? as i32 as i64)
+            },
+            SMALL_BIG_EXT => {
+                self.read_u8()?;
+                let len = self.read_u8()? as usize;
+                self.read_big(len)?
+            },
+            LARGE_BIG_EXT => {
+                self.read_u8()?;
+                let len = self.read_u32()? as usize;
+                self.read_big(len)?
+            },
+            _ => return Ok(None)
+        };
+
+        Ok(Some(integer))
+    }
+
+    /// Returns true and skips the next term if it's the `nil` atom
+    fn read_nil(&mut self) -> Result<bool, Error> {
+        let mut peek = Deserializer { input: self.input };
+        let tag = peek.read_u8()?;
+        if !matches!(tag, ATOM_EXT | SMALL_ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT) || peek.read_atom(tag)? != "nil" {
+            return Ok(false);
+        }
+
+        self.input = peek.input;
+        Ok(true)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if let Some(integer) = self.read_integer()? {
+            return match integer {
+                Integer::Unsigned(value) => visitor.visit_u64(value),
+                Integer::Signed(value) => visitor.visit_i64(value)
+            };
+        }
+
+        let tag = self.read_u8()?;
+        match tag {
+            NEW_FLOAT_EXT => visitor.visit_f64(f64::from_bits(u64::from_be_bytes(self.read_bytes(8)?.try_into().unwrap()))),
+            FLOAT_EXT => {
+                // Old floats are stored as a 31 byte string padded with zeroes
+                let text = str::from_utf8(self.read_bytes(31)?).map_err(|_| Error::InvalidString)?;
+                let value = text.trim_end_matches('\0').trim().parse().map_err(|_| Error::InvalidString)?;
+                visitor.visit_f64(value)
+            },
+            ATOM_EXT | SMALL_ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_UTF8_EXT => match self.read_atom(tag)? {
+                "nil" => visitor.visit_unit(),
+                "true" => visitor.visit_bool(true),
+                "false" => visitor.visit_bool(false),
+                atom => visitor.visit_borrowed_str(atom)
+            },
+            NIL_EXT => visitor.visit_seq(List { de: self, remaining: 0 }),
+            STRING_EXT => {
+                // Lists of small integers are sent as strings
+                let len = self.read_u16()? as usize;
+                let mut seq = SeqDeserializer::new(self.read_bytes(len)?.iter().copied());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            },
+            LIST_EXT => {
+                let len = self.read_u32()? as usize;
+                let value = visitor.visit_seq(List { de: self, remaining: len })?;
+
+                // Proper lists end with an empty list
+                match self.read_u8()? {
+                    NIL_EXT => Ok(value),
+                    tag => Err(Error::UnsupportedTag(tag))
+                }
+            },
+            SMALL_TUPLE_EXT => {
+                let len = self.read_u8()? as usize;
+                visitor.visit_seq(List { de: self, remaining: len })
+            },
+            LARGE_TUPLE_EXT => {
+                let len = self.read_u32()? as usize;
+                visitor.visit_seq(List { de: self, remaining: len })
+            },
+            BINARY_EXT => {
+                let len = self.read_u32()? as usize;
+                let bytes = self.read_bytes(len)?;
+                match str::from_utf8(bytes) {
+                    Ok(text) => visitor.visit_borrowed_str(text),
+                    Err(_) => visitor.visit_borrowed_bytes(bytes)
+                }
+            },
+            MAP_EXT => {
+                let len = self.read_u32()? as usize;
+                visitor.visit_map(List { de: self, remaining: len })
+            },
+            tag => Err(Error::UnsupportedTag(tag))
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.read_nil()? {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // Some ids are sent as integers but stored as strings
+        match self.read_integer()? {
+            Some(Integer::Unsigned(value)) => visitor.visit_string(value.to_string()),
+            Some(Integer::Signed(value)) => visitor.visit_string(value.to_string()),
+            None => self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        match self.peek_tag()? {
+            // Variants with data are maps with a single key, like they are in json
+            MAP_EXT => {
+                self.read_u8()?;
+                match self.read_u32()? {
+                    1 => visitor.visit_enum(Enum { de: self }),
+                    _ => Err(de::Error::custom("expected a map with a single key for an enum"))
+                }
+            },
+            _ => {
+                let variant: String = de::Deserialize::deserialize(&mut *self)?;
+                visitor.visit_enum(variant.into_deserializer())
+            }
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Gives access to the elements of lists and tuples, and the entries of maps
+struct List<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    remaining: usize
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for List<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        // Every element takes at least a byte, so a length that doesn't fit in the input isn't trusted
+        Some(self.remaining.min(self.de.input.len()))
+    }
+}
+
+impl<'de, 'a> de::MapAccess<'de> for List<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining.min(self.de.input.len()))
+    }
+}
+
+/// Gives access to an enum variant stored as a map with a single key
+struct Enum<'a, 'de> {
+    de: &'a mut Deserializer<'de>
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for Enum<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant = seed.deserialize(&mut *self.de)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for Enum<'a, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
@@ -0,0 +1,340 @@
+use std::convert::TryFrom;
+
+use serde::ser::{self, Serialize};
+
+use super::*;
+
+/// Serializes a value into a term, starting with the version byte.
+/// Strings are sent as binaries and `None` as the `nil` atom
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut serializer = Serializer { output: vec![VERSION] };
+    value.serialize(&mut serializer)?;
+    Ok(serializer.output)
+}
+
+pub struct Serializer {
+    output: Vec<u8>
+}
+
+impl Serializer {
+    fn write_atom(&mut self, atom: &str) {
+        self.output.push(SMALL_ATOM_UTF8_EXT);
+        self.output.push(atom.len() as u8);
+        self.output.extend_from_slice(atom.as_bytes());
+    }
+
+    fn write_binary(&mut self, bytes: &[u8]) {
+        self.output.push(BINARY_EXT);
+        self.output.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        self.output.extend_from_slice(bytes);
+    }
+
+    fn write_unsigned(&mut self, value: u64) {
+        if let Ok(value) = u8::try_from(value) {
+            self.output.push(SMALL_INTEGER_EXT);
+            self.output.push(value);
+        } else if let Ok(value) = i32::try_from(value) {
+            self.output.push(INTEGER_EXT);
+            self.output.extend_from_slice(&value.to_be_bytes());
+        } else {
+            self.write_big(0, value);
+        }
+    }
+
+    fn write_signed(&mut self, value: i64) {
+        if value >= 0 {
+            self.write_unsigned(value as u64);
+        } else if let Ok(value) = i32::try_from(value) {
+            self.output.push(INTEGER_EXT);
+            self.output.extend_from_slice(&value.to_be_bytes());
+        } else {
+            self.write_big(1, value.unsigned_abs());
+        }
+    }
+
+    fn write_big(&mut self, sign: u8, value: u64) {
+        // Big integers are stored in little endian without the leading zeroes
+        let digits = value.to_le_bytes();
+        let len = 8 - value.leading_zeros() as usize / 8;
+        self.output.push(SMALL_BIG_EXT);
+        self.output.push(len as u8);
+        self.output.push(sign);
+        self.output.extend_from_slice(&digits[..len]);
+    }
+
+    /// Writes the header of a list or a map, its length is filled in by `Compound::finish`
+    fn start_compound(&mut self, tag: u8) -> Compound<'_> {
+        let start = self.output.len();
+        self.output.push(tag);
+        self.output.extend_from_slice(&[0; 4]);
+        Compound { ser: self, start, len: 0 }
+    }
+
+    /// Variants with data are written as a map with a single key, like they are in json
+    fn start_variant(&mut self, variant: &str) {
+        self.output.push(MAP_EXT);
+        self.output.extend_from_slice(&1u32.to_be_bytes());
+        self.write_binary(variant.as_bytes());
+    }
+}
+
+/// A list or a map that's being written
+pub struct Compound<'a> {
+    ser: &'a mut Serializer,
+    /// the position of the tag in the output
+    start: usize,
+    /// the number of elements or entries written
+    len: u32
+}
+
+impl<'a> Compound<'a> {
+    fn finish(self) {
+        let tag = self.ser.output[self.start];
+        if tag == LIST_EXT && self.len == 0 {
+            // Empty lists are written as nil
+            self.ser.output.truncate(self.start);
+            self.ser.output.push(NIL_EXT);
+            return;
+        }
+
+        self.ser.output[self.start + 1..self.start + 5].copy_from_slice(&self.len.to_be_bytes());
+        if tag == LIST_EXT {
+            self.ser.output.push(NIL_EXT);
+        }
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.write_atom(if v { "true" } else { "false" });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.write_signed(v);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.write_unsigned(v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.output.push(NEW_FLOAT_EXT);
+        self.output.extend_from_slice(&v.to_bits().to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.write_binary(v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.write_binary(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.write_atom("nil");
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _variant_index: u32, variant: &'static str, value: &T) -> Result<(), Error> {
+        self.start_variant(variant);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(self.start_compound(LIST_EXT))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.start_compound(LIST_EXT))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.start_compound(LIST_EXT))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str, _len: usize) -> Result<Compound<'a>, Error> {
+        self.start_variant(variant);
+        Ok(self.start_compound(LIST_EXT))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(self.start_compound(MAP_EXT))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.start_compound(MAP_EXT))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _variant_index: u32, variant: &'static str, _len: usize) -> Result<Compound<'a>, Error> {
+        self.start_variant(variant);
+        Ok(self.start_compound(MAP_EXT))
+    }
+}
+
+impl<'a> ser::SerializeSeq for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.len += 1;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTuple for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl<'a> ser::SerializeMap for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.len += 1;
+        key.serialize(&mut *self.ser)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeMap::end(self)
+    }
+}
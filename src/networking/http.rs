@@ -0,0 +1,358 @@
+//! A client for the REST api, which decodes the error responses sent by discord and waits for rate limits
+
+use std::{fmt, sync::Arc, time::{Duration, SystemTime}};
+
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Deserializer, de::DeserializeOwned};
+use serde_json::Value;
+
+use self::ratelimit::{RateLimiter, Route};
+use super::data::{Snowflake, message::{EditMessage, MessageInfo}, user::UserInfo};
+
+mod ratelimit;
+
+/// How many times a request is retried after being rate limited before giving up
+const MAX_RETRIES: u32 = 5;
+
+/// Messages older than this can't be bulk deleted
+const BULK_DELETE_MAX_AGE: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+/// Sends requests to the REST api with the shared reqwest client, which has the token in its default headers
+#[derive(Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    /// the base url every path is added to, like `https://discord.com/api/v8`
+    api_base: String,
+    /// shared by every clone of the client
+    rate_limiter: Arc<RateLimiter>
+}
+
+/// Which messages of a channel to get, relative to a message
+#[derive(Debug, Clone, Copy)]
+pub enum MessagePosition {
+    /// the most recent messages
+    Latest,
+    /// the messages sent before a message
+    Before(Snowflake)
+}
+
+/// An emoji used to react to a message
+#[derive(Debug, Clone, Copy)]
+pub enum ReactionEmoji<'a> {
+    /// a unicode emoji, like `👍`
+    Unicode(&'a str),
+    /// an emoji of a guild
+    Custom { name: &'a str, id: Snowflake }
+}
+
+/// Why a request to the REST api failed
+#[derive(Debug)]
+pub enum RestError {
+    /// the request couldn't be sent or the response couldn't be read
+    Request(reqwest::Error),
+    /// discord responded with an error
+    Api { status: StatusCode, error: DiscordError },
+    /// the response had an error status without an error body, like when discord is down
+    Status(StatusCode),
+    /// the request body couldn't be encoded or the response body couldn't be decoded
+    Decode(serde_json::Error),
+    /// the request wasn't sent because discord would reject it
+    Invalid(&'static str)
+}
+
+/// The body of an error response.
+/// https://discord.com/developers/docs/reference#error-messages
+#[derive(Debug, Deserialize)]
+pub struct DiscordError {
+    /// https://discord.com/developers/docs/topics/opcodes-and-status-codes#json-json-error-codes, 0 for general errors
+    #[serde(default)]
+    pub code: u32,
+    pub message: String,
+    /// the fields of the request body that were invalid
+    #[serde(default, deserialize_with = "deserialize_field_errors")]
+    pub errors: Vec<FieldError>
+}
+
+/// Why a field of the request body was invalid
+#[derive(Debug)]
+pub struct FieldError {
+    /// the path to the field, like `embed.fields.0.name`
+    pub path: String,
+    /// like `BASE_TYPE_MAX_LENGTH`
+    pub code: String,
+    pub message: String
+}
+
+/// The body of a 429 response
+/// https://discord.com/developers/docs/topics/rate-limits#exceeding-a-rate-limit-rate-limit-response-structure
+#[derive(Deserialize)]
+struct RateLimitInfo {
+    /// seconds to wait before sending another request
+    retry_after: f64,
+    /// whether every request is rate limited instead of only the ones to this route
+    #[serde(default)]
+    global: bool
+}
+
+#[derive(Deserialize)]
+struct FieldErrorInfo {
+    code: String,
+    message: String
+}
+
+/// The errors are nested like the invalid fields, with the errors of each field in `_errors`
+fn deserialize_field_errors<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<FieldError>, D::Error> {
+    fn collect(value: Value, path: &str, errors: &mut Vec<FieldError>) {
+        let fields = match value {
+            Value::Object(fields) => fields,
+            _ => return
+        };
+
+        for (key, value) in fields {
+            if key == "_errors" {
+                let infos: Vec<FieldErrorInfo> = serde_json::from_value(value).unwrap_or_default();
+                errors.extend(infos.into_iter().map(|info| FieldError { path: path.to_string(), code: info.code, message: info.message }));
+            } else if path.is_empty() {
+                collect(value, &key, errors);
+            } else {
+                collect(value, &format!("{}.{}", path, key), errors);
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    collect(Value::deserialize(deserializer)?, "", &mut errors);
+    Ok(errors)
+}
+
+impl ReactionEmoji<'_> {
+    /// The emoji as a segment of a path, custom emojis are `name:id`.
+    /// Every byte that isn't unreserved in urls is percent encoded, which is needed for unicode emojis
+    fn encode(&self) -> String {
+        let emoji = match self {
+            ReactionEmoji::Unicode(emoji) => emoji.to_string(),
+            ReactionEmoji::Custom { name, id } => format!("{}:{}", name, id)
+        };
+        emoji.bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+                _ => format!("%{:02X}", byte)
+            })
+            .collect()
+    }
+}
+
+impl MessagePosition {
+    /// The query parameter added after the limit, empty for the latest messages
+    fn query(&self) -> String {
+        match self {
+            MessagePosition::Latest => String::new(),
+            MessagePosition::Before(id) => format!("&before={}", id)
+        }
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestError::Request(e) => write!(f, "request failed: {}", e),
+            RestError::Api { status, error } => write!(f, "{} ({})", error, status),
+            RestError::Status(status) => write!(f, "request failed with {}", status),
+            RestError::Decode(e) => write!(f, "invalid response: {}", e),
+            RestError::Invalid(reason) => write!(f, "invalid request: {}", reason)
+        }
+    }
+}
+
+impl std::error::Error for RestError {}
+
+impl fmt::Display for DiscordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (error {})", self.message, self.code)?;
+        for error in &self.errors {
+            write!(f, ", {}: {} ({})", error.path, error.message, error.code)?;
+        }
+        Ok(())
+    }
+}
+
+impl HttpClient {
+    pub fn new(client: reqwest::Client, api_base: String) -> Self {
+        HttpClient { client, api_base, rate_limiter: Arc::new(RateLimiter::default()) }
+    }
+
+    /// Sends a request to a path of the api, like `/channels/{channel.id}/messages`, and decodes the response.
+    /// Requests wait for the rate limit of their route and are sent again if they're rate limited anyway.
+    /// Responses without a body are decoded from `null`, so `()` can be used for them
+    pub async fn request<T: DeserializeOwned>(&self, method: Method, path: &str, body: Option<Value>) -> Result<T, RestError> {
+        let route = Route::new(&method, path);
+        let mut retries = 0;
+        let (status, bytes) = loop {
+            let mut bucket = self.rate_limiter.acquire(&route).await;
+
+            let mut request = self.client.request(method.clone(), format!("{}{}", self.api_base, path));
+            if let Some(body) = &body {
+                request = request.json(body);
+            }
+
+            let response = request.send().await.map_err(RestError::Request)?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let bytes = response.bytes().await.map_err(RestError::Request)?;
+            trace!("{} {} responded with {}", method, path, status);
+            self.rate_limiter.update(&route, &mut bucket, &headers);
+
+            if status != StatusCode::TOO_MANY_REQUESTS || retries == MAX_RETRIES {
+                break (status, bytes);
+            }
+
+            // The body says how long to wait more precisely than the Retry-After header
+            let (retry_after, global) = match serde_json::from_slice::<RateLimitInfo>(&bytes) {
+                Ok(info) => (Duration::from_secs_f64(info.retry_after.max(0.0)), info.global),
+                Err(_) => {
+                    let retry_after = headers.get("retry-after").and_then(|value| value.to_str().ok()?.parse().ok()).unwrap_or(1.0);
+                    (Duration::from_secs_f64(retry_after), headers.contains_key("x-ratelimit-global"))
+                }
+            };
+            debug!("{} {} was rate limited, retrying in {:?}", method, path, retry_after);
+            self.rate_limiter.limited(&mut bucket, retry_after, global);
+            retries += 1;
+        };
+
+        if !status.is_success() {
+            return Err(match serde_json::from_slice(&bytes) {
+                Ok(error) => RestError::Api { status, error },
+                Err(_) => RestError::Status(status)
+            });
+        }
+
+        let bytes = if bytes.is_empty() { &b"null"[..] } else { &bytes[..] };
+        serde_json::from_slice(bytes).map_err(RestError::Decode)
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, RestError> {
+        self.request(Method::GET, path, None).await
+    }
+
+    pub async fn post<T: DeserializeOwned>(&self, path: &str, body: Value) -> Result<T, RestError> {
+        self.request(Method::POST, path, Some(body)).await
+    }
+
+    pub async fn put(&self, path: &str) -> Result<(), RestError> {
+        self.request(Method::PUT, path, None).await
+    }
+
+    pub async fn patch<T: DeserializeOwned>(&self, path: &str, body: Value) -> Result<T, RestError> {
+        self.request(Method::PATCH, path, Some(body)).await
+    }
+
+    pub async fn delete(&self, path: &str) -> Result<(), RestError> {
+        self.request(Method::DELETE, path, None).await
+    }
+
+    /// Gets up to `limit` messages of a channel, from 1 to 100, newest first.
+    /// https://discord.com/developers/docs/resources/channel#get-channel-messages
+    pub async fn get_messages(&self, channel_id: Snowflake, position: MessagePosition, limit: u8) -> Result<Vec<MessageInfo>, RestError> {
+        let path = format!("/channels/{}/messages?limit={}{}", channel_id, limit.clamp(1, 100), position.query());
+        self.get(&path).await
+    }
+
+    /// https://discord.com/developers/docs/resources/channel#create-message
+    pub async fn create_message(&self, channel_id: Snowflake, content: &str) -> Result<MessageInfo, RestError> {
+        self.post(&format!("/channels/{}/messages", channel_id), serde_json::json!({ "content": content })).await
+    }
+
+    /// Only messages sent by the current user can be edited, except for their flags.
+    /// https://discord.com/developers/docs/resources/channel#edit-message
+    pub async fn edit_message(&self, channel_id: Snowflake, message_id: Snowflake, edit: &EditMessage) -> Result<MessageInfo, RestError> {
+        let body = serde_json::to_value(edit).map_err(RestError::Decode)?;
+        self.patch(&format!("/channels/{}/messages/{}", channel_id, message_id), body).await
+    }
+
+    /// Deleting messages of other users needs the MANAGE_MESSAGES permission.
+    /// https://discord.com/developers/docs/resources/channel#delete-message
+    pub async fn delete_message(&self, channel_id: Snowflake, message_id: Snowflake) -> Result<(), RestError> {
+        self.delete(&format!("/channels/{}/messages/{}", channel_id, message_id)).await
+    }
+
+    /// Whether a message is recent enough to be bulk deleted
+    pub fn can_bulk_delete(message_id: Snowflake) -> bool {
+        message_id.created_at() >= SystemTime::now() - BULK_DELETE_MAX_AGE
+    }
+
+    /// Deletes 2 to 100 messages at once, none of them can be older than 2 weeks.
+    /// The request isn't sent if discord would reject the ids.
+    /// https://discord.com/developers/docs/resources/channel#bulk-delete-messages
+    pub async fn bulk_delete_messages(&self, channel_id: Snowflake, message_ids: &[Snowflake]) -> Result<(), RestError> {
+        if !(2..=100).contains(&message_ids.len()) {
+            return Err(RestError::Invalid("only 2 to 100 messages can be bulk deleted"));
+        }
+        if !message_ids.iter().all(|id| Self::can_bulk_delete(*id)) {
+            return Err(RestError::Invalid("messages older than 2 weeks can't be bulk deleted"));
+        }
+
+        let path = format!("/channels/{}/messages/bulk-delete", channel_id);
+        self.post(&path, serde_json::json!({ "messages": message_ids })).await
+    }
+
+    /// Reacts to a message as the current user.
+    /// https://discord.com/developers/docs/resources/channel#create-reaction
+    pub async fn add_reaction(&self, channel_id: Snowflake, message_id: Snowflake, emoji: ReactionEmoji<'_>) -> Result<(), RestError> {
+        self.put(&format!("/channels/{}/messages/{}/reactions/{}/@me", channel_id, message_id, emoji.encode())).await
+    }
+
+    /// https://discord.com/developers/docs/resources/channel#delete-own-reaction
+    pub async fn remove_own_reaction(&self, channel_id: Snowflake, message_id: Snowflake, emoji: ReactionEmoji<'_>) -> Result<(), RestError> {
+        self.delete(&format!("/channels/{}/messages/{}/reactions/{}/@me", channel_id, message_id, emoji.encode())).await
+    }
+
+    /// Removes the reaction of another user, which needs the MANAGE_MESSAGES permission.
+    /// https://discord.com/developers/docs/resources/channel#delete-user-reaction
+    pub async fn remove_user_reaction(&self, channel_id: Snowflake, message_id: Snowflake, emoji: ReactionEmoji<'_>, user_id: Snowflake) -> Result<(), RestError> {
+        self.delete(&format!("/channels/{}/messages/{}/reactions/{}/{}", channel_id, message_id, emoji.encode(), user_id)).await
+    }
+
+    /// Gets up to `limit` users that reacted with an emoji, from 1 to 100, with ids after `after`.
+    /// https://discord.com/developers/docs/resources/channel#get-reactions
+    pub async fn get_reactions(&self, channel_id: Snowflake, message_id: Snowflake, emoji: ReactionEmoji<'_>, after: Option<Snowflake>, limit: u8) -> Result<Vec<UserInfo>, RestError> {
+        let mut path = format!("/channels/{}/messages/{}/reactions/{}?limit={}", channel_id, message_id, emoji.encode(), limit.clamp(1, 100));
+        if let Some(after) = after {
+            path.push_str(&format!("&after={}", after));
+        }
+        self.get(&path).await
+    }
+
+    /// Removes the reactions with an emoji, or all of them if there's no emoji. Needs the MANAGE_MESSAGES permission.
+    /// https://discord.com/developers/docs/resources/channel#delete-all-reactions
+    pub async fn clear_reactions(&self, channel_id: Snowflake, message_id: Snowflake, emoji: Option<ReactionEmoji<'_>>) -> Result<(), RestError> {
+        match emoji {
+            Some(emoji) => self.delete(&format!("/channels/{}/messages/{}/reactions/{}", channel_id, message_id, emoji.encode())).await,
+            None => self.delete(&format!("/channels/{}/messages/{}/reactions", channel_id, message_id)).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_position_query() {
+        let id = Snowflake::new(175928847299117063);
+        assert_eq!(MessagePosition::Latest.query(), "");
+        assert_eq!(MessagePosition::Before(id).query(), "&before=175928847299117063");
+    }
+
+    #[test]
+    fn encodes_unicode_emojis() {
+        assert_eq!(ReactionEmoji::Unicode("👍").encode(), "%F0%9F%91%8D");
+        // Emojis with a variation selector keep it
+        assert_eq!(ReactionEmoji::Unicode("❤️").encode(), "%E2%9D%A4%EF%B8%8F");
+    }
+
+    #[test]
+    fn encodes_custom_emojis() {
+        let emoji = ReactionEmoji::Custom { name: "rust_crab", id: Snowflake::new(41771983423143937) };
+        assert_eq!(emoji.encode(), "rust_crab%3A41771983423143937");
+    }
+}
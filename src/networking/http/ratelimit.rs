@@ -0,0 +1,169 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use reqwest::{Method, header::HeaderMap};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// The segments after which an id is a major parameter, requests with different major parameters never share a bucket
+const MAJOR_PARAMETERS: [&str; 3] = ["channels", "guilds", "webhooks"];
+
+/// A route of the api, which decides the rate limit bucket of a request
+pub struct Route {
+    /// the method and path with every id except the major parameter replaced, like `GET /channels/123/messages/{id}`
+    key: String,
+    /// the major parameter with the segment before it, like `channels/123`
+    major: String
+}
+
+impl Route {
+    pub fn new(method: &Method, path: &str) -> Self {
+        // The query doesn't change the bucket
+        let path = path.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        let major = match segments.as_slice() {
+            [resource, id, ..] if MAJOR_PARAMETERS.contains(resource) => format!("{}/{}", resource, id),
+            _ => String::new()
+        };
+
+        // Reactions with every emoji share a bucket
+        let route_segments: Vec<&str> = segments.iter().enumerate()
+            .map(|(i, segment)| match (i, i.checked_sub(1).map(|previous| segments[previous])) {
+                (1, _) if !major.is_empty() => *segment,
+                (_, Some("reactions")) => "{emoji}",
+                _ if segment.parse::<u64>().is_ok() => "{id}",
+                _ => *segment
+            })
+            .collect();
+
+        Route {
+            key: format!("{} /{}", method, route_segments.join("/")),
+            major
+        }
+    }
+}
+
+/// How many requests are left in a bucket and when it resets
+#[derive(Default)]
+struct BucketState {
+    remaining: Option<u32>,
+    reset_at: Option<Instant>
+}
+
+/// Waits before requests that would be rate limited.
+/// Requests in the same bucket are sent one after another, so the headers of each response are known before the next one is sent
+#[derive(Default)]
+pub struct RateLimiter {
+    /// the bucket every route was put in, routes that weren't requested yet are their own bucket
+    routes: Mutex<HashMap<String, String>>,
+    buckets: Mutex<HashMap<String, Arc<AsyncMutex<BucketState>>>>,
+    /// until when every request is rate limited
+    global_reset_at: Mutex<Option<Instant>>
+}
+
+/// Holds a bucket while its request is being sent, other requests in the bucket wait until it's dropped
+pub struct BucketGuard {
+    state: OwnedMutexGuard<BucketState>
+}
+
+fn header<T: std::str::FromStr>(headers: &HeaderMap, name: &str) -> Option<T> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+impl RateLimiter {
+    /// Waits until a request can be sent on the route, and holds its bucket until the guard is dropped
+    pub async fn acquire(&self, route: &Route) -> BucketGuard {
+        let bucket = {
+            let bucket_id = self.routes.lock().unwrap().get(&route.key).cloned().unwrap_or_else(|| route.key.clone());
+            self.buckets.lock().unwrap().entry(bucket_id).or_default().clone()
+        };
+
+        let mut state = bucket.lock_owned().await;
+        if let (Some(0), Some(reset_at)) = (state.remaining, state.reset_at) {
+            if reset_at > Instant::now() {
+                debug!("Waiting {:?} for the rate limit of {}", reset_at - Instant::now(), route.key);
+                tokio::time::sleep_until(reset_at.into()).await;
+            }
+            state.remaining = None;
+        }
+
+        // The global limit is checked last since it can be hit while waiting for the bucket
+        let global_reset_at = *self.global_reset_at.lock().unwrap();
+        if let Some(reset_at) = global_reset_at.filter(|reset_at| *reset_at > Instant::now()) {
+            debug!("Waiting {:?} for the global rate limit", reset_at - Instant::now());
+            tokio::time::sleep_until(reset_at.into()).await;
+        }
+
+        BucketGuard { state }
+    }
+
+    /// Updates the bucket of a route from the `X-RateLimit-*` headers of its response
+    pub fn update(&self, route: &Route, guard: &mut BucketGuard, headers: &HeaderMap) {
+        if let Some(remaining) = header(headers, "x-ratelimit-remaining") {
+            guard.state.remaining = Some(remaining);
+        }
+        if let Some(reset_after) = header::<f64>(headers, "x-ratelimit-reset-after") {
+            guard.state.reset_at = Some(Instant::now() + Duration::from_secs_f64(reset_after));
+        }
+
+        // Routes with the same bucket hash share their limit, unless their major parameters are different
+        let hash = match headers.get("x-ratelimit-bucket").and_then(|hash| hash.to_str().ok()) {
+            Some(h) => h,
+            None => return
+        };
+        let bucket_id = format!("{}:{}", hash, route.major);
+        let previous = self.routes.lock().unwrap().insert(route.key.clone(), bucket_id.clone());
+        if previous.as_ref() != Some(&bucket_id) {
+            let mut buckets = self.buckets.lock().unwrap();
+            let bucket = buckets.get(previous.as_ref().unwrap_or(&route.key)).cloned();
+            if let Some(bucket) = bucket {
+                buckets.entry(bucket_id).or_insert(bucket);
+            }
+        }
+    }
+
+    /// Stops requests after a 429 response, every request if the global limit was hit or only the ones in the bucket otherwise
+    pub fn limited(&self, guard: &mut BucketGuard, retry_after: Duration, global: bool) {
+        let reset_at = Instant::now() + retry_after;
+        if global {
+            warn!("Hit the global rate limit, waiting {:?}", retry_after);
+            *self.global_reset_at.lock().unwrap() = Some(reset_at);
+        } else {
+            guard.state.remaining = Some(0);
+            guard.state.reset_at = Some(reset_at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(method: Method, path: &str) -> String {
+        Route::new(&method, path).key
+    }
+
+    #[test]
+    fn major_parameters_split_buckets() {
+        assert_eq!(key(Method::GET, "/channels/1/messages/2"), "GET /channels/1/messages/{id}");
+        assert_eq!(key(Method::GET, "/channels/1/messages/2"), key(Method::GET, "/channels/1/messages/3"));
+        assert_ne!(key(Method::GET, "/channels/1/messages/2"), key(Method::GET, "/channels/2/messages/2"));
+        assert_ne!(key(Method::GET, "/channels/1/messages/2"), key(Method::DELETE, "/channels/1/messages/2"));
+        assert_eq!(Route::new(&Method::GET, "/guilds/5/members/6").major, "guilds/5");
+        assert_eq!(Route::new(&Method::GET, "/users/@me").major, "");
+    }
+
+    #[test]
+    fn query_is_ignored() {
+        assert_eq!(key(Method::GET, "/channels/1/messages?limit=50&before=2"), "GET /channels/1/messages");
+    }
+
+    #[test]
+    fn emojis_are_normalized() {
+        let unicode = key(Method::PUT, "/channels/1/messages/2/reactions/%F0%9F%91%8D/@me");
+        let custom = key(Method::PUT, "/channels/1/messages/3/reactions/rust%3A4/@me");
+        assert_eq!(unicode, "PUT /channels/1/messages/{id}/reactions/{emoji}/@me");
+        assert_eq!(unicode, custom);
+        // Removing the reaction of another user still keeps the user id apart from the emoji
+        assert_eq!(key(Method::DELETE, "/channels/1/messages/2/reactions/%F0%9F%91%8D/5"), "DELETE /channels/1/messages/{id}/reactions/{emoji}/{id}");
+        assert_eq!(key(Method::DELETE, "/channels/1/messages/2/reactions"), "DELETE /channels/1/messages/{id}/reactions");
+    }
+}
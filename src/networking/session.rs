@@ -0,0 +1,193 @@
+use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use tokio::sync::Notify;
+
+use super::{data::gateway::GatewayIntents, shard::{EventCounters, IdentifyLimiter}};
+
+/// State of a gateway session that has to survive across websocket connections,
+/// used to resume the session instead of identifying again after a disconnect
+pub struct GatewaySession {
+    /// the token used to identify and resume the session
+    token: String,
+    /// the intents sent when identifying as a bot
+    intents: GatewayIntents,
+    /// `[shard_id, num_shards]` of the connection
+    shard: [u32; 2],
+    /// shared by all shards to respect the identify concurrency
+    identify_limiter: Arc<IdentifyLimiter>,
+    /// shared by all shards to count events that couldn't be delivered right away
+    event_counters: Arc<EventCounters>,
+    state: Mutex<SessionState>,
+    /// notified when the current connection should be closed and opened again
+    reconnect: Notify
+}
+
+#[derive(Default)]
+struct SessionState {
+    /// the session id received in the READY event
+    session_id: Option<String>,
+    /// the url to connect to when resuming, received in the READY event
+    resume_gateway_url: Option<String>,
+    /// the last sequence number received from the gateway
+    sequence: Option<i32>,
+    /// incremented every time a new websocket connection is made
+    connection: u32,
+    /// when the last heartbeat was sent
+    last_heartbeat: Option<Instant>,
+    /// whether the gateway acknowledged the last heartbeat
+    heartbeat_acked: bool,
+    /// how long the gateway took to acknowledge the last heartbeat
+    latency: Option<Duration>,
+    /// the connection that the last reconnect was requested for
+    reconnect_connection: Option<u32>
+}
+
+impl GatewaySession {
+    pub fn new(token: String, intents: GatewayIntents, shard: [u32; 2], identify_limiter: Arc<IdentifyLimiter>, event_counters: Arc<EventCounters>) -> Self {
+        GatewaySession {
+            token,
+            intents,
+            shard,
+            identify_limiter,
+            event_counters,
+            state: Mutex::new(SessionState::default()),
+            reconnect: Notify::new()
+        }
+    }
+
+    /// The token sent to the gateway, without the `Bot ` prefix used by the REST api
+    pub fn token(&self) -> &str {
+        self.token.strip_prefix("Bot ").unwrap_or(&self.token)
+    }
+
+    /// Bot tokens start with `Bot `
+    pub fn is_bot(&self) -> bool {
+        self.token.starts_with("Bot ")
+    }
+
+    pub fn intents(&self) -> GatewayIntents {
+        self.intents
+    }
+
+    pub fn shard(&self) -> [u32; 2] {
+        self.shard
+    }
+
+    pub fn shard_id(&self) -> u32 {
+        self.shard[0]
+    }
+
+    pub fn event_counters(&self) -> &EventCounters {
+        &self.event_counters
+    }
+
+    /// Waits until this shard is allowed to identify
+    pub async fn wait_for_identify(&self) {
+        self.identify_limiter.wait(self.shard_id()).await;
+    }
+
+    pub fn session_id(&self) -> Option<String> {
+        self.state.lock().unwrap().session_id.clone()
+    }
+
+    /// Stores the session from the READY event, so it can be resumed later
+    pub fn set_session(&self, session_id: String, resume_gateway_url: Option<String>) {
+        let mut state = self.state.lock().unwrap();
+        state.session_id = Some(session_id);
+        state.resume_gateway_url = resume_gateway_url;
+    }
+
+    pub fn resume_gateway_url(&self) -> Option<String> {
+        self.state.lock().unwrap().resume_gateway_url.clone()
+    }
+
+    pub fn sequence(&self) -> Option<i32> {
+        self.state.lock().unwrap().sequence
+    }
+
+    /// Stores the sequence number of a received payload if it's newer than the current one
+    pub fn update_sequence(&self, sequence: i32) {
+        let mut state = self.state.lock().unwrap();
+        if state.sequence < Some(sequence) {
+            state.sequence = Some(sequence);
+        }
+    }
+
+    /// Returns true if there's a session that can be resumed
+    pub fn can_resume(&self) -> bool {
+        self.state.lock().unwrap().session_id.is_some()
+    }
+
+    /// Forgets the session id and sequence, so the next connection has to identify again
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.session_id = None;
+        state.resume_gateway_url = None;
+        state.sequence = None;
+    }
+
+    /// Asks the connection task to close the websocket of `connection` and open a new one.
+    /// Nothing happens if the shard already moved on to another connection
+    pub fn request_reconnect(&self, connection: u32) {
+        let mut state = self.state.lock().unwrap();
+        if state.connection != connection {
+            debug!("Ignoring a reconnect requested for a previous connection");
+            return;
+        }
+        state.reconnect_connection = Some(connection);
+        drop(state);
+        // Stores a permit if the connection task isn't waiting yet, so the request isn't lost
+        self.reconnect.notify_one();
+    }
+
+    /// Waits until a reconnect is requested with `request_reconnect` for `connection`
+    pub async fn reconnect_requested(&self, connection: u32) {
+        loop {
+            self.reconnect.notified().await;
+            if self.state.lock().unwrap().reconnect_connection == Some(connection) {
+                return;
+            }
+        }
+    }
+
+    /// The id of the current websocket connection
+    pub fn connection(&self) -> u32 {
+        self.state.lock().unwrap().connection
+    }
+
+    /// Marks the start of a new websocket connection and returns its id
+    pub fn new_connection(&self) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        state.connection = state.connection.wrapping_add(1);
+        state.last_heartbeat = None;
+        state.heartbeat_acked = true;
+        state.connection
+    }
+
+    /// Records that a heartbeat was sent.
+    /// Returns false if the previous heartbeat was never acknowledged, meaning the connection is dead
+    pub fn heartbeat_sent(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state.heartbeat_acked {
+            return false;
+        }
+
+        state.last_heartbeat = Some(Instant::now());
+        state.heartbeat_acked = false;
+        true
+    }
+
+    /// Records that the gateway acknowledged the last heartbeat and how long it took
+    pub fn heartbeat_acked(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.heartbeat_acked = true;
+        if let Some(sent) = state.last_heartbeat {
+            state.latency = Some(sent.elapsed());
+        }
+    }
+
+    /// How long the gateway took to acknowledge the last heartbeat
+    pub fn latency(&self) -> Option<Duration> {
+        self.state.lock().unwrap().latency
+    }
+}
@@ -0,0 +1,187 @@
+use std::{cmp, sync::{Arc, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant}};
+
+use tokio::{sync::{mpsc, Mutex}, task::JoinHandle, time};
+
+use crate::config::Config;
+use super::{connect_to_discord, discovery, http::HttpClient, session::GatewaySession};
+use super::data::gateway::{GatewayCloseCode, GatewayPayload, SessionStartLimit};
+
+/// How long a shard has to wait after another shard in the same bucket identified
+const IDENTIFY_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the session start limit resets
+const SESSION_START_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A payload received from the gateway by one of the shards
+#[derive(Debug)]
+pub struct ShardPayload {
+    pub shard_id: u32,
+    /// the websocket connection of the shard that received the payload, used to ignore payloads of older connections
+    pub connection: u32,
+    pub payload: GatewayPayload
+}
+
+/// A single gateway connection
+pub struct Shard {
+    pub session: Arc<GatewaySession>,
+    /// sends payloads to the websocket of this shard
+    pub write_tx: futures_channel::mpsc::UnboundedSender<GatewayPayload>
+}
+
+/// Limits how often shards can identify.
+/// Shards are put in `max_concurrency` buckets by `shard_id % max_concurrency`, only one shard per bucket can identify every 5 seconds.
+/// Every identify also uses up one of the session starts given by the REST api, once they run out shards wait until they reset
+pub struct IdentifyLimiter {
+    /// when a shard in each bucket last identified
+    buckets: Vec<Mutex<Option<Instant>>>,
+    /// `None` for users, who don't get a session start limit
+    session_starts: Mutex<Option<SessionStarts>>
+}
+
+struct SessionStarts {
+    total: u32,
+    remaining: u32,
+    reset_at: time::Instant
+}
+
+impl IdentifyLimiter {
+    pub fn new(limit: Option<&SessionStartLimit>) -> Self {
+        let max_concurrency = limit.map_or(1, |limit| limit.max_concurrency);
+        IdentifyLimiter {
+            buckets: (0..cmp::max(max_concurrency, 1)).map(|_| Mutex::new(None)).collect(),
+            session_starts: Mutex::new(limit.map(|limit| SessionStarts {
+                total: limit.total,
+                remaining: limit.remaining,
+                reset_at: time::Instant::now() + Duration::from_millis(limit.reset_after)
+            }))
+        }
+    }
+
+    /// Waits until the shard is allowed to identify
+    pub async fn wait(&self, shard_id: u32) {
+        if let Some(starts) = self.session_starts.lock().await.as_mut() {
+            if time::Instant::now() >= starts.reset_at {
+                starts.reset();
+            }
+            if starts.remaining == 0 {
+                warn!("Reached the session start limit, waiting {:?}", starts.reset_at.saturating_duration_since(time::Instant::now()));
+                time::sleep_until(starts.reset_at).await;
+                starts.reset();
+            }
+            starts.remaining = starts.remaining.saturating_sub(1);
+            debug!("{} of {} session starts remaining", starts.remaining, starts.total);
+        }
+
+        let mut last_identify = self.buckets[shard_id as usize % self.buckets.len()].lock().await;
+        if let Some(last) = *last_identify {
+            tokio::time::sleep_until((last + IDENTIFY_INTERVAL).into()).await;
+        }
+        *last_identify = Some(Instant::now());
+    }
+}
+
+impl SessionStarts {
+    fn reset(&mut self) {
+        self.remaining = self.total;
+        self.reset_at = time::Instant::now() + SESSION_START_INTERVAL;
+    }
+}
+
+/// Counts received events that couldn't be handed to the message handler right away, across all shards
+#[derive(Default)]
+pub struct EventCounters {
+    /// events that had to wait for the message handler to catch up
+    delayed: AtomicU64,
+    /// events that were lost because the message handler stopped
+    dropped: AtomicU64
+}
+
+impl EventCounters {
+    /// Records a delayed event and returns the number of delayed events so far
+    pub fn add_delayed(&self) -> u64 {
+        self.delayed.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Records a dropped event and returns the number of dropped events so far
+    pub fn add_dropped(&self) -> u64 {
+        self.dropped.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn delayed(&self) -> u64 {
+        self.delayed.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Runs a gateway connection for every shard and keeps their sessions and write senders
+pub struct ShardManager {
+    shards: Vec<Shard>
+}
+
+impl ShardManager {
+    /// Spawns a connection for every shard, payloads received by any of them are sent to `read_tx`.
+    /// The gateway info is only asked for once, all shards connect to its url and share its session start limit.
+    /// Bots use the number of shards in the config or the one recommended by the gateway, users always use a single one.
+    /// The returned handles finish when their shard stops, with the close code if it was closed for good
+    pub async fn start(http: HttpClient, config: Config, read_tx: mpsc::Sender<ShardPayload>) -> (ShardManager, Vec<JoinHandle<Result<(), GatewayCloseCode>>>) {
+        let bot = config.token.starts_with("Bot ");
+        let info = discovery::get_gateway_info(&http, &config, bot).await;
+        let shard_count = if bot { cmp::max(config.shards.or(info.shards).unwrap_or(1), 1) } else { 1 };
+        let max_concurrency = info.session_start_limit.as_ref().map_or(1, |limit| limit.max_concurrency);
+        info!("Starting {} shard(s) with an identify concurrency of {}", shard_count, max_concurrency);
+
+        let identify_limiter = Arc::new(IdentifyLimiter::new(info.session_start_limit.as_ref()));
+        let event_counters = Arc::new(EventCounters::default());
+        let mut shards = Vec::new();
+        let mut handles = Vec::new();
+        for shard_id in 0..shard_count {
+            let session = Arc::new(GatewaySession::new(config.token.clone(), config.intents, [shard_id, shard_count], identify_limiter.clone(), event_counters.clone()));
+            let (write_tx, write_rx) = futures_channel::mpsc::unbounded::<GatewayPayload>();
+
+            handles.push(tokio::spawn(connect_to_discord(write_rx, read_tx.clone(), session.clone(), info.url.clone(), config.clone())));
+            shards.push(Shard { session, write_tx });
+        }
+
+        (ShardManager { shards }, handles)
+    }
+
+    pub fn shard(&self, shard_id: u32) -> Option<&Shard> {
+        self.shards.get(shard_id as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(remaining: u32) -> SessionStartLimit {
+        SessionStartLimit { total: 5, remaining, reset_after: 1000, max_concurrency: 16 }
+    }
+
+    #[tokio::test]
+    async fn counts_down_session_starts() {
+        time::pause();
+        let limiter = IdentifyLimiter::new(Some(&limit(2)));
+        let start = time::Instant::now();
+        limiter.wait(0).await;
+        limiter.wait(1).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // The third shard has to wait for the limit to reset, then the whole day's starts are available again
+        limiter.wait(2).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(1000));
+        assert_eq!(limiter.session_starts.lock().await.as_ref().unwrap().remaining, 4);
+    }
+
+    #[tokio::test]
+    async fn users_have_no_session_start_limit() {
+        time::pause();
+        let limiter = IdentifyLimiter::new(None);
+        let start = time::Instant::now();
+        limiter.wait(0).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert!(limiter.session_starts.lock().await.is_none());
+    }
+}
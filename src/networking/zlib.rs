@@ -0,0 +1,106 @@
+use flate2::{Decompress, DecompressError, FlushDecompress};
+
+/// Every complete message sent with `compress=zlib-stream` ends with this suffix
+const ZLIB_SUFFIX: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Decompresses a `zlib-stream` gateway connection.
+/// The inflate context is shared between every message of the connection, so a new one is needed for every connection
+pub struct ZlibStream {
+    inflater: Decompress,
+    /// compressed data of a message that hasn't been fully received yet
+    buffer: Vec<u8>
+}
+
+impl ZlibStream {
+    pub fn new() -> Self {
+        ZlibStream {
+            inflater: Decompress::new(true),
+            buffer: Vec::new()
+        }
+    }
+
+    /// Adds a binary frame to the stream.
+    /// Returns the decompressed message once all of its frames have been received
+    pub fn push(&mut self, data: &[u8]) -> Result<Option<Vec<u8>>, DecompressError> {
+        self.buffer.extend_from_slice(data);
+        if !self.buffer.ends_with(&ZLIB_SUFFIX) {
+            return Ok(None);
+        }
+
+        let mut output = Vec::with_capacity(self.buffer.len() * 4);
+        let mut offset = 0;
+        loop {
+            let total_in = self.inflater.total_in();
+            let total_out = self.inflater.total_out();
+            self.inflater.decompress_vec(&self.buffer[offset..], &mut output, FlushDecompress::Sync)?;
+
+            let consumed = (self.inflater.total_in() - total_in) as usize;
+            let produced = self.inflater.total_out() - total_out;
+            offset += consumed;
+
+            // Make room for more data if the output is full, otherwise stop once everything was read
+            if output.len() == output.capacity() {
+                output.reserve(output.capacity());
+            } else if offset >= self.buffer.len() || (consumed == 0 && produced == 0) {
+                break;
+            }
+        }
+
+        self.buffer.clear();
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{Compress, Compression, FlushCompress};
+
+    use super::*;
+
+    /// Compresses a message like the gateway does, with a context shared by every message of the connection
+    fn compress(compressor: &mut Compress, message: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(message.len() + 64);
+        compressor.compress_vec(message, &mut output, FlushCompress::Sync).unwrap();
+        assert!(output.ends_with(&ZLIB_SUFFIX));
+        output
+    }
+
+    #[test]
+    fn buffers_split_messages() {
+        let mut compressor = Compress::new(Compression::default(), true);
+        let message = br#"{"op":10,"d":{"heartbeat_interval":41250},"s":null,"t":null}"#;
+        let compressed = compress(&mut compressor, message);
+
+        let mut stream = ZlibStream::new();
+        let (first, rest) = compressed.split_at(compressed.len() / 3);
+        let (second, last) = rest.split_at(rest.len() / 2);
+        assert_eq!(stream.push(first).unwrap(), None);
+        assert_eq!(stream.push(second).unwrap(), None);
+        assert_eq!(stream.push(last).unwrap().as_deref(), Some(&message[..]));
+    }
+
+    #[test]
+    fn shares_the_context_between_messages() {
+        let mut compressor = Compress::new(Compression::default(), true);
+        let first = br#"{"op":11,"d":null,"s":null,"t":null}"#;
+        let second = br#"{"op":11,"d":null,"s":null,"t":null}"#;
+        let first_compressed = compress(&mut compressor, first);
+        let second_compressed = compress(&mut compressor, second);
+        // The second message refers back to the first one instead of repeating it
+        assert!(second_compressed.len() < first_compressed.len());
+
+        let mut stream = ZlibStream::new();
+        assert_eq!(stream.push(&first_compressed).unwrap().as_deref(), Some(&first[..]));
+        assert_eq!(stream.push(&second_compressed).unwrap().as_deref(), Some(&second[..]));
+    }
+
+    #[test]
+    fn grows_the_output() {
+        let mut compressor = Compress::new(Compression::default(), true);
+        let message = br#"{"id":"175928847299117063"},"#.repeat(1000);
+        let compressed = compress(&mut compressor, &message);
+        assert!(compressed.len() * 4 < message.len());
+
+        assert_eq!(ZlibStream::new().push(&compressed).unwrap(), Some(message));
+    }
+}
@@ -1,32 +1,53 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
 use fltk::*;
 
+use self::{chat::Chat, guilds::GuildList};
+use crate::cache::Cache;
+use crate::networking::http::HttpClient;
+
+mod channels;
+mod chat;
+mod guilds;
+
 #[derive(Clone)]
 pub struct RustcordUI {
     pub app: app::App,
     pub window: window::DoubleWindow,
-    pub guilds: frame::Frame,
+    pub guilds: browser::HoldBrowser,
     pub top_bar: frame::Frame,
-    pub channels: frame::Frame,
+    pub channels: browser::HoldBrowser,
     pub info: frame::Frame,
-    pub members: frame::Frame,
+    pub members: browser::HoldBrowser,
     pub chat_messages: browser::HoldBrowser,
     pub chat_send_button: button::ReturnButton,
-    pub chat_text_input: input::Input
+    pub chat_text_input: input::Input,
+    /// the channel shown in chat_messages and its messages, in the same order
+    chat: Arc<Mutex<Chat>>,
+    /// the guilds shown in guilds, and the channels and members shown in channels and members
+    guild_list: Arc<Mutex<GuildList>>,
+    /// used to look up users and channels that events only contain the ids of
+    cache: Arc<Cache>,
+    http: HttpClient
 }
 
-impl Default for RustcordUI {
-    fn default() -> Self {
+impl RustcordUI {
+    pub fn new(cache: Arc<Cache>, http: HttpClient) -> Self {
         let mut ui = RustcordUI {
             app: app::App::default(),
             window: window::Window::new(100, 100, 1000, 500, "Rustcord"),
-            guilds: frame::Frame::default().with_size(50, 500).with_label("servers"),
+            guilds: browser::HoldBrowser::default().with_size(50, 500),
             top_bar: frame::Frame::default().with_pos(50, 0).with_size(950, 50).with_label("top bar"),
-            channels: frame::Frame::default().with_pos(50, 50).with_size(200, 400).with_label("channels"),
+            channels: browser::HoldBrowser::default().with_pos(50, 50).with_size(200, 400),
             info: frame::Frame::default().with_pos(50, 450).with_size(200, 50).with_label("info"),
-            members: frame::Frame::default().with_pos(750, 50).with_size(250, 450).with_label("members"),
+            members: browser::HoldBrowser::default().with_pos(750, 50).with_size(250, 450),
             chat_messages: browser::HoldBrowser::default().with_size(500, 400).with_pos(250, 50),
             chat_send_button: button::ReturnButton::default().with_pos(700, 450).with_size(50, 50).with_label("send"),
-            chat_text_input: input::Input::default().with_pos(250, 450).with_size(450, 50)
+            chat_text_input: input::Input::default().with_pos(250, 450).with_size(450, 50),
+            chat: Arc::new(Mutex::new(Chat::default())),
+            guild_list: Arc::new(Mutex::new(GuildList::default())),
+            cache,
+            http
         };
 
         // Set the framing of the different widgets
@@ -36,6 +57,10 @@ impl Default for RustcordUI {
         ui.info.set_frame(FrameType::EngravedBox);
         ui.members.set_frame(FrameType::EngravedBox);
 
+        ui.set_guild_select_callback();
+        ui.set_channel_select_callback();
+        ui.set_chat_event_handler();
+
         ui.window.make_resizable(true);
         ui.window.end();
         ui.window.show();
@@ -52,26 +77,54 @@ impl Default for RustcordUI {
 
         ui
     }
-}
 
-impl RustcordUI {
-    pub fn new() -> Self {
-        Default::default()
+    /// Shows the gateway latency in the info box
+    pub fn set_ping(&mut self, latency: Duration) {
+        self.info.set_label(&format!("ping: {}ms", latency.as_millis()));
+        self.info.redraw();
+        app::awake();
+    }
+
+    /// Shows an error in the top bar
+    pub fn show_error(&mut self, message: &str) {
+        self.top_bar.set_label_color(Color::Red);
+        self.top_bar.set_label(message);
+        self.top_bar.redraw();
+        app::awake();
+    }
+
+    /// Shows a status message in the top bar
+    pub fn show_status(&mut self, message: &str) {
+        self.top_bar.set_label_color(Color::Black);
+        self.top_bar.set_label(message);
+        self.top_bar.redraw();
+        app::awake();
     }
 
-    /// Changes the callback of self.chat_send_button to send the content to a discord message.
-    /// Will only show the message after the websocket acknowledges it exists
-    pub fn set_send_callback_to_discord(&mut self, client: reqwest::Client) {
-        let self_c = self.clone();
+    /// Changes the callback of self.chat_send_button to send the content to a discord message in the open channel.
+    /// Will only show the message after the websocket acknowledges it exists, the error is shown if it can't be sent
+    pub fn set_send_callback_to_discord(&mut self) {
+        let mut self_c = self.clone();
         self.chat_send_button.set_callback(move || {
-            let client = client.clone();
+            let http = self_c.http.clone();
+            let mut ui = self_c.clone();
             // Only add the value if the input isn't empty
             if !self_c.chat_text_input.value().is_empty() {
+                let channel = self_c.chat.lock().unwrap().channel;
+                let channel_id = match channel {
+                    Some(id) => id,
+                    None => {
+                        self_c.show_error("Open a channel to send messages");
+                        return;
+                    }
+                };
                 // Clone the content of the input so there are no race conditions
                 let content = self_c.chat_text_input.value().clone();
                 tokio::spawn(async move {
-                    // TODO: Change the channel id to the selected channel
-                    crate::sendable::send_message(client, content, "829119138475671602".to_string()).await;
+                    if let Err(e) = http.create_message(channel_id, &content).await {
+                        error!("Failed to send a message: {}", e);
+                        ui.show_error(&format!("Couldn't send the message: {}", e));
+                    }
                 });
                 self_c.chat_text_input.set_value("");
             }
@@ -0,0 +1,182 @@
+use fltk::*;
+
+use super::RustcordUI;
+use crate::cache::Cache;
+use crate::networking::data::{Snowflake, channel::{ChannelInfo, ChannelTypes}, user::UserInfo};
+
+/// A guild channel or a DM shown in the channel list
+pub struct ChannelEntry {
+    id: Snowflake,
+    /// the name of a guild channel or group DM, DMs don't have one
+    name: Option<String>,
+    channel_type: ChannelTypes,
+    position: i32,
+    /// the category the channel is in
+    parent_id: Option<Snowflake>,
+    /// the ids and usernames of the recipients of a DM
+    recipients: Vec<(Snowflake, String)>
+}
+
+fn username(user: &UserInfo) -> String {
+    user.username.clone().unwrap_or_else(|| "missing_username".to_string())
+}
+
+impl ChannelEntry {
+    /// The cache is used to find the recipients of DMs that only contain their ids
+    pub(super) fn new(channel: &ChannelInfo, cache: &Cache) -> Self {
+        let mut recipients: Vec<(Snowflake, String)> = channel.recipients.iter()
+            .map(|user| (user.id, username(user)))
+            .collect();
+        for id in channel.recipient_ids.iter().flatten() {
+            let name = cache.user(id).map_or_else(|| "unknown_user".to_string(), |user| username(&user));
+            recipients.push((*id, name));
+        }
+
+        ChannelEntry {
+            id: channel.id,
+            name: channel.name.clone(),
+            channel_type: channel.channel_type,
+            position: channel.position.unwrap_or(0),
+            parent_id: channel.parent_id,
+            recipients
+        }
+    }
+
+    /// The id of the channel if it has messages that can be shown in the chat
+    fn text_channel_id(&self) -> Option<Snowflake> {
+        match self.channel_type {
+            ChannelTypes::GuildText | ChannelTypes::GuildNews | ChannelTypes::DM | ChannelTypes::GroupDm => Some(self.id),
+            ChannelTypes::GuildCategory | ChannelTypes::GuildVoice | ChannelTypes::GuildStore => None
+        }
+    }
+
+    /// The line showing this channel, indented if it's in a category.
+    /// `@.` stops the rest of the line from being read as formatting
+    fn line(&self, indent: bool) -> String {
+        let indent = if indent { "    " } else { "" };
+        let name = self.name.clone().unwrap_or_default();
+        match self.channel_type {
+            ChannelTypes::GuildText | ChannelTypes::GuildNews => format!("@.{}#{}", indent, name),
+            ChannelTypes::GuildCategory => format!("@b@.{}", name.to_uppercase()),
+            ChannelTypes::GuildVoice => format!("@.{}{} (voice)", indent, name),
+            ChannelTypes::GuildStore => format!("@.{}{} (store)", indent, name),
+            // DMs are named after their recipients unless a group DM was given a name
+            ChannelTypes::DM | ChannelTypes::GroupDm => match &self.name {
+                Some(name) if !name.is_empty() => format!("@.{}", name),
+                _ => format!("@.{}", self.recipients.iter().map(|(_, name)| name.as_str()).collect::<Vec<_>>().join(", "))
+            }
+        }
+    }
+}
+
+/// Adds or replaces a channel in a list of channels
+fn set_channel(channels: &mut Vec<ChannelEntry>, entry: ChannelEntry) {
+    match channels.iter_mut().find(|channel| channel.id == entry.id) {
+        Some(existing) => *existing = entry,
+        None => channels.push(entry)
+    }
+}
+
+impl RustcordUI {
+    /// Makes clicking a channel open it in the chat
+    pub(super) fn set_channel_select_callback(&mut self) {
+        let mut self_c = self.clone();
+        self.channels.set_callback(move || {
+            let line = self_c.channels.value() as usize;
+            let channel_id = self_c.guild_list.lock().unwrap().channel_lines.get(line.wrapping_sub(1)).copied().flatten();
+            if let Some(channel_id) = channel_id {
+                self_c.open_channel(channel_id);
+            }
+        });
+    }
+
+    /// Adds the DMs received in READY, or replaces them if they're already shown
+    pub fn add_private_channels(&mut self, channels: &[ChannelInfo]) {
+        let mut list = self.guild_list.lock().unwrap();
+        for channel in channels {
+            set_channel(&mut list.private_channels, ChannelEntry::new(channel, &self.cache));
+        }
+        drop(list);
+        self.redraw_channels();
+    }
+
+    /// Adds a channel or replaces it if it's already shown, channels without a guild id are DMs
+    pub fn set_channel(&mut self, channel: &ChannelInfo) {
+        let entry = ChannelEntry::new(channel, &self.cache);
+        let mut list = self.guild_list.lock().unwrap();
+        match &channel.guild_id {
+            Some(guild_id) => if let Some(guild) = list.guild_mut(guild_id) {
+                set_channel(&mut guild.channels, entry);
+            },
+            None => set_channel(&mut list.private_channels, entry)
+        }
+        drop(list);
+        self.redraw_channels();
+    }
+
+    pub fn remove_channel(&mut self, channel: &ChannelInfo) {
+        let mut list = self.guild_list.lock().unwrap();
+        match &channel.guild_id {
+            Some(guild_id) => if let Some(guild) = list.guild_mut(guild_id) {
+                guild.channels.retain(|existing| existing.id != channel.id);
+            },
+            None => list.private_channels.retain(|existing| existing.id != channel.id)
+        }
+        drop(list);
+        self.redraw_channels();
+    }
+
+    /// Adds a user to a group DM
+    pub fn add_recipient(&mut self, channel_id: &Snowflake, user: &UserInfo) {
+        if let Some(channel) = self.guild_list.lock().unwrap().private_channels.iter_mut().find(|channel| &channel.id == channel_id) {
+            channel.recipients.retain(|(id, _)| id != &user.id);
+            channel.recipients.push((user.id, username(user)));
+        }
+        self.redraw_channels();
+    }
+
+    /// Removes a user from a group DM
+    pub fn remove_recipient(&mut self, channel_id: &Snowflake, user_id: &Snowflake) {
+        if let Some(channel) = self.guild_list.lock().unwrap().private_channels.iter_mut().find(|channel| &channel.id == channel_id) {
+            channel.recipients.retain(|(id, _)| id != user_id);
+        }
+        self.redraw_channels();
+    }
+
+    /// Shows the channels of the selected guild, or the DMs if no guild is selected.
+    /// Guild channels are shown like a tree, channels without a category come first, then every category with its channels
+    pub(super) fn redraw_channels(&mut self) {
+        self.channels.clear();
+        let mut list = self.guild_list.lock().unwrap();
+        let mut lines = Vec::new();
+        match list.selected.as_ref().and_then(|id| list.guilds.iter().find(|guild| &guild.id == id)) {
+            Some(guild) => {
+                let mut channels: Vec<&ChannelEntry> = guild.channels.iter().collect();
+                channels.sort_by_key(|channel| (channel.channel_type == ChannelTypes::GuildVoice, channel.position));
+
+                let (categories, channels): (Vec<&ChannelEntry>, Vec<&ChannelEntry>) = channels.into_iter()
+                    .partition(|channel| channel.channel_type == ChannelTypes::GuildCategory);
+                for channel in channels.iter().filter(|channel| channel.parent_id.is_none()) {
+                    self.channels.add(&channel.line(false));
+                    lines.push(channel.text_channel_id());
+                }
+                for category in categories {
+                    self.channels.add(&category.line(false));
+                    lines.push(None);
+                    for channel in channels.iter().filter(|channel| channel.parent_id.as_ref() == Some(&category.id)) {
+                        self.channels.add(&channel.line(true));
+                        lines.push(channel.text_channel_id());
+                    }
+                }
+            },
+            None => for channel in &list.private_channels {
+                self.channels.add(&channel.line(false));
+                lines.push(channel.text_channel_id());
+            }
+        }
+        list.channel_lines = lines;
+        drop(list);
+        self.channels.redraw();
+        app::awake();
+    }
+}
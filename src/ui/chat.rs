@@ -0,0 +1,558 @@
+use std::future::Future;
+
+use fltk::*;
+
+use super::RustcordUI;
+use crate::networking::{data::{Snowflake, channel::ChannelPermissionFlags, event::{MessageReactionData, MessageUpdateData}, guild::GuildEmojiInfo, message::{EditMessage, MessageInfo}, timestamp}, http::{HttpClient, MessagePosition, ReactionEmoji, RestError}};
+
+/// How many messages are loaded at once when opening a channel or scrolling up
+const MESSAGE_PAGE_SIZE: u8 = 50;
+
+/// How many users are shown when listing who reacted with an emoji
+const REACTION_USERS_LIMIT: u8 = 25;
+
+/// How many users are requested at once when looking for the one whose reaction is removed
+const REACTION_USERS_PAGE_SIZE: u8 = 100;
+
+/// The channel shown in the chat and its loaded messages
+#[derive(Default)]
+pub struct Chat {
+    /// the channel whose messages are shown, messages sent in other channels are ignored
+    pub(super) channel: Option<Snowflake>,
+    /// shown in `chat_messages` oldest first, each message takes one line or two if it has reactions
+    messages: Vec<ChatMessage>,
+    /// true while a page of messages is being fetched
+    loading: bool,
+    /// true once the first message of the channel has been loaded
+    reached_start: bool
+}
+
+/// A message shown in the chat.
+/// It's kept so the line can be changed when the message is edited, deleted or reacted to
+pub struct ChatMessage {
+    id: Snowflake,
+    /// used to only show the actions for messages of the current user on them
+    author_id: Snowflake,
+    /// when the message was sent in the timezone of the user
+    time: String,
+    author: String,
+    content: String,
+    edited: bool,
+    pinned: bool,
+    reactions: Vec<ChatReaction>
+}
+
+/// The number of reactions to a message with a single emoji
+#[derive(Clone)]
+struct ChatReaction {
+    /// only custom emojis have an id
+    emoji_id: Option<Snowflake>,
+    emoji_name: String,
+    count: i32,
+    /// whether the current user reacted with the emoji
+    me: bool
+}
+
+/// Something that can be done to a message from its menu
+enum MessageAction {
+    Edit,
+    Delete,
+    BulkDelete(Vec<Snowflake>),
+    /// reacts with an emoji typed by the user
+    AddReaction,
+    /// reacts with the emoji of a reaction, or removes the reaction of the current user
+    ToggleReaction(usize),
+    /// shows the users that reacted with the emoji of a reaction
+    ListReactions(usize),
+    /// removes the reaction of a user typed by the moderator
+    RemoveUserReaction(usize),
+    ClearReactions
+}
+
+impl ChatReaction {
+    fn is_emoji(&self, emoji: &GuildEmojiInfo) -> bool {
+        match &emoji.id {
+            Some(id) => self.emoji_id.as_ref() == Some(id),
+            None => self.emoji_id.is_none() && emoji.name.as_deref() == Some(self.emoji_name.as_str())
+        }
+    }
+
+    /// The emoji like it's written, custom emojis are shown by their name
+    fn emoji_text(&self) -> String {
+        match self.emoji_id {
+            Some(_) => format!(":{}:", self.emoji_name),
+            None => self.emoji_name.clone()
+        }
+    }
+}
+
+impl ChatMessage {
+    fn new(message: &MessageInfo, timezone_offset: i32) -> Self {
+        let username = message.author.username.as_deref().unwrap_or("missing_username");
+        let discriminator = message.author.discriminator.as_deref().unwrap_or("missing_discriminator");
+        let reactions = message.reactions.iter().flatten()
+            .map(|reaction| ChatReaction {
+                emoji_id: reaction.emoji.id,
+                emoji_name: reaction.emoji.name.clone().unwrap_or_default(),
+                count: reaction.count,
+                me: reaction.me
+            })
+            .collect();
+
+        ChatMessage {
+            id: message.id,
+            author_id: message.author.id,
+            time: timestamp::format_local(&message.timestamp, timezone_offset, "%H:%M"),
+            author: format!("{}#{}", username, discriminator),
+            content: message.content.clone(),
+            edited: message.edited_timestamp.is_some(),
+            pinned: message.pinned,
+            reactions
+        }
+    }
+
+    /// The lines showing this message, the reactions are shown on a line under it
+    fn lines(&self) -> Vec<String> {
+        let mut line = format!("[{}] <{}>: {}", self.time, self.author, self.content);
+        if self.pinned {
+            line.insert_str(0, "[pinned] ");
+        }
+        if self.edited {
+            line.push_str(" (edited)");
+        }
+        if self.reactions.is_empty() {
+            return vec![line];
+        }
+
+        // The reactions of the current user are in brackets
+        let reactions: Vec<String> = self.reactions.iter()
+            .map(|reaction| match reaction.me {
+                true => format!("[{} {}]", reaction.emoji_text(), reaction.count),
+                false => format!("{} {}", reaction.emoji_text(), reaction.count)
+            })
+            .collect();
+        vec![line, format!("        {}", reactions.join("  "))]
+    }
+
+    fn line_count(&self) -> usize {
+        if self.reactions.is_empty() { 1 } else { 2 }
+    }
+}
+
+impl Chat {
+    /// The line of `chat_messages` the message at an index starts on
+    fn first_line(&self, index: usize) -> u32 {
+        self.messages[..index].iter().map(ChatMessage::line_count).sum::<usize>() as u32 + 1
+    }
+
+    /// The index of the message shown on a line of `chat_messages`
+    fn message_at_line(&self, line: u32) -> Option<usize> {
+        let mut first_line = 1;
+        for (index, message) in self.messages.iter().enumerate() {
+            first_line += message.line_count() as u32;
+            if line < first_line {
+                return Some(index);
+            }
+        }
+        None
+    }
+}
+
+impl RustcordUI {
+    /// Loads the earlier messages of the open channel when the chat is scrolled past the top,
+    /// and shows the actions for a message when it's right clicked
+    pub(super) fn set_chat_event_handler(&mut self) {
+        let mut self_c = self.clone();
+        self.chat_messages.handle(move |event| {
+            // The browser selects the clicked line when the button is pressed
+            if event == Event::Released && app::event_mouse_button() == app::Mouse::Right {
+                self_c.show_message_actions();
+                return true;
+            }
+
+            let scrolled_up = match event {
+                Event::MouseWheel => app::event_dy() < 0,
+                Event::Released => true,
+                _ => false
+            };
+            if scrolled_up && self_c.chat_messages.position() == 0 {
+                self_c.load_earlier_messages();
+            }
+            false
+        });
+    }
+
+    /// Shows a channel in the chat and loads its latest messages
+    pub fn open_channel(&mut self, channel_id: Snowflake) {
+        let mut chat = self.chat.lock().unwrap();
+        if chat.channel == Some(channel_id) {
+            return;
+        }
+        *chat = Chat { channel: Some(channel_id), loading: true, ..Chat::default() };
+        drop(chat);
+
+        self.chat_messages.clear();
+        self.redraw_chat();
+        self.fetch_messages(channel_id, MessagePosition::Latest);
+    }
+
+    /// Loads the page of messages before the oldest one that's shown
+    fn load_earlier_messages(&mut self) {
+        let mut chat = self.chat.lock().unwrap();
+        let (channel_id, oldest) = match (chat.channel, chat.messages.first()) {
+            (Some(channel_id), Some(oldest)) if !chat.loading && !chat.reached_start => (channel_id, oldest.id),
+            _ => return
+        };
+        chat.loading = true;
+        drop(chat);
+
+        self.fetch_messages(channel_id, MessagePosition::Before(oldest));
+    }
+
+    /// Fetches a page of messages in another task and adds them to the top of the chat
+    fn fetch_messages(&self, channel_id: Snowflake, position: MessagePosition) {
+        let http = self.http.clone();
+        let mut ui = self.clone();
+        tokio::spawn(async move {
+            match http.get_messages(channel_id, position, MESSAGE_PAGE_SIZE).await {
+                Ok(messages) => ui.add_earlier_messages(channel_id, &messages),
+                Err(e) => {
+                    error!("Failed to load the messages of channel {}: {}", channel_id, e);
+                    let mut chat = ui.chat.lock().unwrap();
+                    if chat.channel == Some(channel_id) {
+                        chat.loading = false;
+                    }
+                    drop(chat);
+                    ui.show_error(&format!("Couldn't load the messages: {}", e));
+                }
+            }
+        });
+    }
+
+    /// Shows a menu with the actions for the selected message, it can only be edited or deleted if the current user sent it
+    /// and the reactions of other users can only be removed with the MANAGE_MESSAGES permission.
+    /// The chat is changed when the websocket sends the update, like for sent messages
+    fn show_message_actions(&mut self) {
+        let current_user_id = match self.cache.current_user() {
+            Some(user) => user.id,
+            None => return
+        };
+        let chat = self.chat.lock().unwrap();
+        let (channel_id, index) = match (chat.channel, chat.message_at_line(self.chat_messages.value())) {
+            (Some(channel_id), Some(index)) => (channel_id, index),
+            _ => return
+        };
+        let message = &chat.messages[index];
+        // Empty in DMs, where messages can't be bulk deleted and reactions of other users can't be removed
+        let can_manage_messages = self.cache.permissions(&channel_id).contains(ChannelPermissionFlags::MANAGE_MESSAGES);
+
+        let mut actions = Vec::new();
+        if message.author_id == current_user_id {
+            actions.push(("Edit".to_string(), MessageAction::Edit));
+            actions.push(("Delete".to_string(), MessageAction::Delete));
+        }
+        if message.author_id == current_user_id && can_manage_messages {
+            // The latest messages of the current user, up to where bulk delete stops working
+            let recent_ids: Vec<Snowflake> = chat.messages.iter().rev()
+                .filter(|message| message.author_id == current_user_id)
+                .map(|message| message.id)
+                .take_while(|id| HttpClient::can_bulk_delete(*id))
+                .take(100)
+                .collect();
+            if recent_ids.len() >= 2 {
+                actions.push((format!("Delete my last {} messages", recent_ids.len()), MessageAction::BulkDelete(recent_ids)));
+            }
+        }
+        actions.push(("Add a reaction".to_string(), MessageAction::AddReaction));
+        for (i, reaction) in message.reactions.iter().enumerate() {
+            let toggle = match reaction.me {
+                true => format!("Remove my {} reaction", reaction.emoji_text()),
+                false => format!("React with {}", reaction.emoji_text())
+            };
+            actions.push((toggle, MessageAction::ToggleReaction(i)));
+            actions.push((format!("Who reacted with {}", reaction.emoji_text()), MessageAction::ListReactions(i)));
+            if can_manage_messages {
+                actions.push((format!("Remove someone's {} reaction", reaction.emoji_text()), MessageAction::RemoveUserReaction(i)));
+            }
+        }
+        if !message.reactions.is_empty() && can_manage_messages {
+            actions.push(("Remove all reactions".to_string(), MessageAction::ClearReactions));
+        }
+        // The chat can't stay locked while the menu is open, since events are still handled
+        let (message_id, content, mut reactions) = (message.id, message.content.clone(), message.reactions.clone());
+        drop(chat);
+
+        let labels: Vec<&str> = actions.iter().map(|(label, _)| label.as_str()).collect();
+        let (x, y) = app::event_coords();
+        let chosen = menu::MenuItem::new(&labels).popup(x, y).and_then(|item| item.label());
+        let action = match actions.into_iter().find(|(label, _)| Some(label) == chosen.as_ref()) {
+            Some((_, action)) => action,
+            None => return
+        };
+
+        let http = self.http.clone();
+        match action {
+            MessageAction::Edit => {
+                if let Some(content) = dialog::input_default("Edit the message", &content) {
+                    let edit = EditMessage { content: Some(content), ..EditMessage::default() };
+                    self.spawn_message_action("edit the message", async move {
+                        http.edit_message(channel_id, message_id, &edit).await.map(|_| ())
+                    });
+                }
+            },
+            MessageAction::Delete => self.spawn_message_action("delete the message", async move {
+                http.delete_message(channel_id, message_id).await
+            }),
+            MessageAction::BulkDelete(mut ids) => {
+                // Messages could have become too old while the menu was open
+                ids.retain(|id| HttpClient::can_bulk_delete(*id));
+                self.spawn_message_action("delete the messages", async move {
+                    http.bulk_delete_messages(channel_id, &ids).await
+                });
+            },
+            MessageAction::AddReaction => {
+                // Custom emojis are typed like `name:id`
+                let emoji = match dialog::input_default("React with an emoji", "") {
+                    Some(emoji) if !emoji.trim().is_empty() => emoji.trim().to_string(),
+                    _ => return
+                };
+                self.spawn_message_action("add the reaction", async move {
+                    let emoji = match emoji.rsplit_once(':').and_then(|(name, id)| Some((name, id.parse().ok()?))) {
+                        Some((name, id)) => ReactionEmoji::Custom { name: name.trim_matches(':'), id },
+                        None => ReactionEmoji::Unicode(&emoji)
+                    };
+                    http.add_reaction(channel_id, message_id, emoji).await
+                });
+            },
+            MessageAction::ToggleReaction(i) => {
+                let reaction = reactions.swap_remove(i);
+                self.spawn_message_action("change the reaction", async move {
+                    let emoji = reaction_emoji(reaction.emoji_id, &reaction.emoji_name);
+                    match reaction.me {
+                        true => http.remove_own_reaction(channel_id, message_id, emoji).await,
+                        false => http.add_reaction(channel_id, message_id, emoji).await
+                    }
+                });
+            },
+            MessageAction::ListReactions(i) => {
+                let reaction = reactions.swap_remove(i);
+                let mut ui = self.clone();
+                self.spawn_message_action("get the reactions", async move {
+                    let emoji = reaction_emoji(reaction.emoji_id, &reaction.emoji_name);
+                    let users = http.get_reactions(channel_id, message_id, emoji, None, REACTION_USERS_LIMIT).await?;
+                    let names: Vec<&str> = users.iter().filter_map(|user| user.username.as_deref()).collect();
+                    ui.show_status(&format!("Reacted with {}: {}", reaction.emoji_text(), names.join(", ")));
+                    Ok(())
+                });
+            },
+            MessageAction::RemoveUserReaction(i) => {
+                let reaction = reactions.swap_remove(i);
+                let username = match dialog::input_default(&format!("Remove the {} reaction of the user named", reaction.emoji_text()), "") {
+                    Some(username) if !username.trim().is_empty() => username.trim().to_string(),
+                    _ => return
+                };
+                let mut ui = self.clone();
+                self.spawn_message_action("remove the reaction", async move {
+                    let emoji = reaction_emoji(reaction.emoji_id, &reaction.emoji_name);
+                    // The reactions only contain a count, so the users are listed to find the id of the one with the name
+                    let mut after = None;
+                    loop {
+                        let users = http.get_reactions(channel_id, message_id, emoji, after, REACTION_USERS_PAGE_SIZE).await?;
+                        if let Some(user) = users.iter().find(|user| user.username.as_deref() == Some(username.as_str())) {
+                            return http.remove_user_reaction(channel_id, message_id, emoji, user.id).await;
+                        }
+                        match users.last() {
+                            Some(last) if users.len() == REACTION_USERS_PAGE_SIZE as usize => after = Some(last.id),
+                            _ => break
+                        }
+                    }
+                    ui.show_error(&format!("Nobody named {} reacted with {}", username, reaction.emoji_text()));
+                    Ok(())
+                });
+            },
+            MessageAction::ClearReactions => self.spawn_message_action("remove the reactions", async move {
+                http.clear_reactions(channel_id, message_id, None).await
+            })
+        }
+    }
+
+    /// Sends a request for a message action in another task and shows the error if it fails
+    fn spawn_message_action(&self, action: &'static str, request: impl Future<Output = Result<(), RestError>> + Send + 'static) {
+        let mut ui = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = request.await {
+                error!("Failed to {}: {}", action, e);
+                ui.show_error(&format!("Couldn't {}: {}", action, e));
+            }
+        });
+    }
+
+    /// Adds a page of messages, newest first, above the messages that are shown.
+    /// The page is ignored if another channel was opened while it was being fetched
+    fn add_earlier_messages(&mut self, channel_id: Snowflake, messages: &[MessageInfo]) {
+        let mut chat = self.chat.lock().unwrap();
+        if chat.channel != Some(channel_id) {
+            return;
+        }
+        chat.loading = false;
+        chat.reached_start = messages.len() < MESSAGE_PAGE_SIZE as usize;
+
+        let first_page = chat.messages.is_empty();
+        let timezone_offset = self.cache.timezone_offset();
+        let mut added_lines = 0;
+        for message in messages {
+            // Messages created while the page was loading are already shown at the bottom
+            if chat.messages.iter().any(|shown| shown.id == message.id) {
+                continue;
+            }
+            let message = ChatMessage::new(message, timezone_offset);
+            let lines = message.lines();
+            for line in lines.iter().rev() {
+                self.chat_messages.insert(1, line);
+            }
+            added_lines += lines.len() as u32;
+            chat.messages.insert(0, message);
+        }
+
+        // Keep showing the same messages after earlier ones were added above them
+        if first_page {
+            self.chat_messages.bottom_line(self.chat_messages.size());
+        } else {
+            self.chat_messages.top_line(added_lines + 1);
+        }
+        drop(chat);
+        self.redraw_chat();
+    }
+
+    /// Adds a message to the bottom of the chat if it was sent in the open channel
+    pub fn add_message(&mut self, message: &MessageInfo) {
+        let mut chat = self.chat.lock().unwrap();
+        if chat.channel != Some(message.channel_id) {
+            return;
+        }
+
+        let message = ChatMessage::new(message, self.cache.timezone_offset());
+        for line in message.lines() {
+            self.chat_messages.add(&line);
+        }
+        chat.messages.push(message);
+        drop(chat);
+        self.redraw_chat();
+    }
+
+    /// Changes the parts of a message that were sent in a MESSAGE_UPDATE
+    pub fn update_message(&mut self, update: &MessageUpdateData) {
+        self.change_message(&update.id, |message| {
+            if let Some(content) = &update.content {
+                message.content = content.clone();
+            }
+            if update.edited_timestamp.is_some() {
+                message.edited = true;
+            }
+            if let Some(pinned) = update.pinned {
+                message.pinned = pinned;
+            }
+        });
+    }
+
+    /// Removes messages from the chat, ids of messages that aren't shown are ignored
+    pub fn delete_messages(&mut self, ids: &[Snowflake]) {
+        let mut chat = self.chat.lock().unwrap();
+        // Remove from the bottom up so the lines of the remaining messages don't change
+        for index in (0..chat.messages.len()).rev() {
+            if ids.contains(&chat.messages[index].id) {
+                let first_line = chat.first_line(index);
+                for _ in 0..chat.messages[index].line_count() {
+                    self.chat_messages.remove(first_line);
+                }
+                chat.messages.remove(index);
+            }
+        }
+        drop(chat);
+        self.redraw_chat();
+    }
+
+    /// Counts a new reaction to a message
+    pub fn add_reaction(&mut self, reaction: &MessageReactionData) {
+        let me = self.cache.current_user().map(|user| user.id) == Some(reaction.user_id);
+        let emoji = &reaction.emoji;
+        self.change_message(&reaction.message_id, |message| {
+            match message.reactions.iter_mut().find(|reaction| reaction.is_emoji(emoji)) {
+                Some(reaction) => {
+                    reaction.count += 1;
+                    reaction.me |= me;
+                },
+                None => message.reactions.push(ChatReaction {
+                    emoji_id: emoji.id,
+                    emoji_name: emoji.name.clone().unwrap_or_default(),
+                    count: 1,
+                    me
+                })
+            }
+        });
+    }
+
+    /// Stops counting a reaction to a message
+    pub fn remove_reaction(&mut self, reaction: &MessageReactionData) {
+        let me = self.cache.current_user().map(|user| user.id) == Some(reaction.user_id);
+        let emoji = &reaction.emoji;
+        self.change_message(&reaction.message_id, |message| {
+            if let Some(reaction) = message.reactions.iter_mut().find(|reaction| reaction.is_emoji(emoji)) {
+                reaction.count -= 1;
+                reaction.me &= !me;
+            }
+            message.reactions.retain(|reaction| reaction.count > 0);
+        });
+    }
+
+    /// Removes the reactions to a message with an emoji, or all of them if there's no emoji
+    pub fn remove_reactions(&mut self, message_id: &Snowflake, emoji: Option<&GuildEmojiInfo>) {
+        self.change_message(message_id, |message| {
+            match emoji {
+                Some(emoji) => message.reactions.retain(|reaction| !reaction.is_emoji(emoji)),
+                None => message.reactions.clear()
+            }
+        });
+    }
+
+    /// Calls `change` on a shown message and updates its lines, does nothing if the message isn't shown
+    fn change_message(&mut self, id: &Snowflake, change: impl FnOnce(&mut ChatMessage)) {
+        let mut chat = self.chat.lock().unwrap();
+        let index = match chat.messages.iter().position(|message| &message.id == id) {
+            Some(i) => i,
+            None => return
+        };
+
+        let first_line = chat.first_line(index);
+        let old_line_count = chat.messages[index].line_count();
+        change(&mut chat.messages[index]);
+
+        // The reactions line is added or removed when the first reaction is added or the last one is removed
+        let lines = chat.messages[index].lines();
+        for (i, line) in lines.iter().enumerate() {
+            if i < old_line_count {
+                self.chat_messages.set_text(first_line + i as u32, line);
+            } else {
+                self.chat_messages.insert(first_line + i as u32, line);
+            }
+        }
+        for _ in lines.len()..old_line_count {
+            self.chat_messages.remove(first_line + lines.len() as u32);
+        }
+        drop(chat);
+        self.redraw_chat();
+    }
+
+    fn redraw_chat(&mut self) {
+        self.chat_messages.redraw();
+        app::awake();
+    }
+}
+
+/// The emoji of a reaction, for requests sent after the chat was unlocked
+fn reaction_emoji(emoji_id: Option<Snowflake>, emoji_name: &str) -> ReactionEmoji<'_> {
+    match emoji_id {
+        Some(id) => ReactionEmoji::Custom { name: emoji_name, id },
+        None => ReactionEmoji::Unicode(emoji_name)
+    }
+}
@@ -0,0 +1,283 @@
+use fltk::*;
+
+use super::{RustcordUI, channels::ChannelEntry};
+use crate::cache::{Cache, Presence};
+use crate::networking::data::{Snowflake, guild::{GuildInfo, GuildMemberInfo, GuildRoleInfo}, user::UserInfo};
+
+/// The guilds shown in the sidebar and the members of the selected one
+#[derive(Default)]
+pub struct GuildList {
+    /// line 1 of the guilds browser opens the DMs, line `n` shows the guild at index `n - 2`
+    pub(super) guilds: Vec<GuildEntry>,
+    /// the DMs of the user
+    pub(super) private_channels: Vec<ChannelEntry>,
+    /// the guild whose channels and members are shown, the DMs are shown if there's none
+    pub(super) selected: Option<Snowflake>,
+    /// the channel shown on every line of the channels browser, starting at line 1. Channels without messages are `None`
+    pub(super) channel_lines: Vec<Option<Snowflake>>
+}
+
+pub(super) struct GuildEntry {
+    pub(super) id: Snowflake,
+    name: String,
+    /// true while the guild is unavailable due to an outage
+    unavailable: bool,
+    roles: Vec<RoleEntry>,
+    members: Vec<MemberEntry>,
+    pub(super) channels: Vec<ChannelEntry>
+}
+
+struct RoleEntry {
+    id: Snowflake,
+    name: String,
+    position: i32
+}
+
+struct MemberEntry {
+    user_id: Snowflake,
+    /// the nickname of the member, or their username if they don't have one
+    name: String,
+    roles: Vec<Snowflake>
+}
+
+impl RoleEntry {
+    fn new(role: &GuildRoleInfo) -> Option<Self> {
+        Some(RoleEntry {
+            id: role.id?,
+            name: role.name.clone(),
+            position: role.position
+        })
+    }
+}
+
+impl MemberEntry {
+    fn new(user: &UserInfo, nick: Option<&String>, roles: &[Snowflake]) -> Self {
+        MemberEntry {
+            user_id: user.id,
+            name: nick.or(user.username.as_ref()).cloned().unwrap_or_else(|| "missing_username".to_string()),
+            roles: roles.to_vec()
+        }
+    }
+}
+
+impl GuildEntry {
+    fn new(guild: &GuildInfo, cache: &Cache) -> Self {
+        let members = guild.members.iter().flatten()
+            .filter_map(|member| Some(MemberEntry::new(member.user.as_ref()?, member.nick.as_ref(), &member.roles)))
+            .collect();
+
+        GuildEntry {
+            id: guild.id,
+            name: guild.name.clone(),
+            unavailable: guild.unavailable.unwrap_or(false),
+            roles: guild.roles.iter().filter_map(RoleEntry::new).collect(),
+            members,
+            channels: guild.channels.iter().flatten().map(|channel| ChannelEntry::new(channel, cache)).collect()
+        }
+    }
+
+    /// The line showing this guild in the sidebar, the first letter of every word in its name like the icon of a guild without one
+    fn line(&self) -> String {
+        let acronym: String = self.name.split_whitespace().filter_map(|word| word.chars().next()).collect();
+        // Unavailable guilds are shown in italics, `@.` stops the rest of the line from being read as formatting
+        if self.unavailable {
+            format!("@i@.{}", acronym)
+        } else {
+            format!("@.{}", acronym)
+        }
+    }
+
+    /// The line showing a member in the member list, with their highest role and their cached presence.
+    /// The status is only shown if it isn't online, presences aren't received at all without the GUILD_PRESENCES intent
+    fn member_line(&self, member: &MemberEntry, presence: Option<Presence>) -> String {
+        let top_role = self.roles.iter()
+            .filter(|role| member.roles.contains(&role.id))
+            .max_by_key(|role| role.position);
+
+        let mut line = match top_role {
+            Some(role) => format!("@.{} [{}]", member.name, role.name),
+            None => format!("@.{}", member.name)
+        };
+        if let Some(presence) = presence {
+            if presence.status != "online" {
+                line.push_str(&format!(" ({})", presence.status));
+            }
+            if let Some(activity) = presence.activities.first() {
+                line.push_str(&format!(" - {}", activity.name));
+            }
+        }
+        line
+    }
+}
+
+impl GuildList {
+    pub(super) fn guild_mut(&mut self, id: &Snowflake) -> Option<&mut GuildEntry> {
+        self.guilds.iter_mut().find(|guild| &guild.id == id)
+    }
+}
+
+impl RustcordUI {
+    /// Makes clicking a guild in the sidebar show its channels and members, or the DMs if the first line is clicked
+    pub(super) fn set_guild_select_callback(&mut self) {
+        let mut self_c = self.clone();
+        self.guilds.set_callback(move || {
+            let selected = match self_c.guilds.value() {
+                0 => return,
+                1 => None,
+                line => match self_c.guild_list.lock().unwrap().guilds.get(line as usize - 2) {
+                    Some(guild) => Some(guild.id),
+                    None => return
+                }
+            };
+            self_c.guild_list.lock().unwrap().selected = selected;
+            self_c.redraw_channels();
+            self_c.redraw_members();
+        });
+    }
+
+    /// Adds the guilds received in the READY of a shard, or replaces them if they're already shown.
+    /// The guilds of the other shards are kept
+    pub fn add_guilds<'a>(&mut self, guilds: impl Iterator<Item = &'a GuildInfo>) {
+        let mut list = self.guild_list.lock().unwrap();
+        for guild in guilds {
+            let entry = GuildEntry::new(guild, &self.cache);
+            match list.guild_mut(&guild.id) {
+                Some(existing) => *existing = entry,
+                None => list.guilds.push(entry)
+            }
+        }
+        drop(list);
+        self.redraw_guilds();
+    }
+
+    /// Adds a guild that was joined or became available again, or replaces it if it's already shown
+    pub fn add_guild(&mut self, guild: &GuildInfo) {
+        let entry = GuildEntry::new(guild, &self.cache);
+        let mut list = self.guild_list.lock().unwrap();
+        match list.guild_mut(&guild.id) {
+            Some(existing) => *existing = entry,
+            None => list.guilds.push(entry)
+        }
+        drop(list);
+        self.redraw_guilds();
+    }
+
+    /// Changes the name and roles of a guild, GUILD_UPDATE doesn't contain the members or channels so they're kept
+    pub fn update_guild(&mut self, guild: &GuildInfo) {
+        if let Some(existing) = self.guild_list.lock().unwrap().guild_mut(&guild.id) {
+            existing.name = guild.name.clone();
+            existing.roles = guild.roles.iter().filter_map(RoleEntry::new).collect();
+        }
+        self.redraw_guilds();
+    }
+
+    /// Marks a guild as unavailable during an outage, or removes it if the user left or was removed from it
+    pub fn remove_guild(&mut self, id: &Snowflake, unavailable: bool) {
+        let mut list = self.guild_list.lock().unwrap();
+        if unavailable {
+            if let Some(guild) = list.guild_mut(id) {
+                guild.unavailable = true;
+            }
+        } else {
+            list.guilds.retain(|guild| &guild.id != id);
+            if list.selected.as_ref() == Some(id) {
+                list.selected = None;
+            }
+        }
+        drop(list);
+        self.redraw_guilds();
+    }
+
+    /// Adds members to a guild, members that are already shown are replaced
+    pub fn add_members(&mut self, guild_id: &Snowflake, members: &[GuildMemberInfo]) {
+        if let Some(guild) = self.guild_list.lock().unwrap().guild_mut(guild_id) {
+            for member in members {
+                let user = match &member.user {
+                    Some(u) => u,
+                    None => continue
+                };
+                guild.members.retain(|entry| entry.user_id != user.id);
+                guild.members.push(MemberEntry::new(user, member.nick.as_ref(), &member.roles));
+            }
+        }
+        self.redraw_members();
+    }
+
+    /// Changes the nickname and roles of a member
+    pub fn update_member(&mut self, guild_id: &Snowflake, user: &UserInfo, nick: Option<&String>, roles: &[Snowflake]) {
+        if let Some(guild) = self.guild_list.lock().unwrap().guild_mut(guild_id) {
+            let entry = MemberEntry::new(user, nick, roles);
+            match guild.members.iter_mut().find(|member| member.user_id == user.id) {
+                Some(member) => *member = entry,
+                None => guild.members.push(entry)
+            }
+        }
+        self.redraw_members();
+    }
+
+    pub fn remove_member(&mut self, guild_id: &Snowflake, user_id: &Snowflake) {
+        if let Some(guild) = self.guild_list.lock().unwrap().guild_mut(guild_id) {
+            guild.members.retain(|member| &member.user_id != user_id);
+        }
+        self.redraw_members();
+    }
+
+    /// Shows a changed presence in the member list, the cache already has it
+    pub fn update_presence(&mut self, user_id: &Snowflake) {
+        let list = self.guild_list.lock().unwrap();
+        let shown = list.selected.as_ref()
+            .and_then(|id| list.guilds.iter().find(|guild| &guild.id == id))
+            .map_or(false, |guild| guild.members.iter().any(|member| &member.user_id == user_id));
+        drop(list);
+        if shown {
+            self.redraw_members();
+        }
+    }
+
+    /// Adds a role to a guild or replaces it if it already exists
+    pub fn set_role(&mut self, guild_id: &Snowflake, role: &GuildRoleInfo) {
+        if let (Some(guild), Some(entry)) = (self.guild_list.lock().unwrap().guild_mut(guild_id), RoleEntry::new(role)) {
+            guild.roles.retain(|existing| existing.id != entry.id);
+            guild.roles.push(entry);
+        }
+        self.redraw_members();
+    }
+
+    /// Removes a role from a guild and from its members
+    pub fn remove_role(&mut self, guild_id: &Snowflake, role_id: &Snowflake) {
+        if let Some(guild) = self.guild_list.lock().unwrap().guild_mut(guild_id) {
+            guild.roles.retain(|role| &role.id != role_id);
+            for member in &mut guild.members {
+                member.roles.retain(|role| role != role_id);
+            }
+        }
+        self.redraw_members();
+    }
+
+    fn redraw_guilds(&mut self) {
+        self.guilds.clear();
+        self.guilds.add("@bDMs");
+        for guild in &self.guild_list.lock().unwrap().guilds {
+            self.guilds.add(&guild.line());
+        }
+        self.guilds.redraw();
+        self.redraw_channels();
+        self.redraw_members();
+    }
+
+    /// Shows the members of the selected guild, sorted by name
+    fn redraw_members(&mut self) {
+        self.members.clear();
+        let list = self.guild_list.lock().unwrap();
+        if let Some(guild) = list.selected.as_ref().and_then(|id| list.guilds.iter().find(|guild| &guild.id == id)) {
+            let mut lines: Vec<String> = guild.members.iter().map(|member| guild.member_line(member, self.cache.presence(&member.user_id))).collect();
+            lines.sort_by_key(|line| line.to_lowercase());
+            for line in lines {
+                self.members.add(&line);
+            }
+        }
+        drop(list);
+        self.members.redraw();
+        app::awake();
+    }
+}